@@ -0,0 +1,207 @@
+use wgpu_bootstrap::{
+    cgmath::{self, InnerSpace, Matrix4, Point3, Rad, Vector3},
+    winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
+};
+
+use crate::scene::CameraConfig;
+
+// =========================================================================================
+//                                    ORBIT CAMERA
+// =========================================================================================
+// The camera turns around a target point at some distance: dragging changes the yaw and
+// the pitch, scrolling changes the distance and panning moves the target in the view
+// plane. The matrices are rebuilt from these values every frame, with the current
+// aspect ratio of the window.
+// =========================================================================================
+
+// Same layout as `CameraUniform` in the render shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view: [[f32; 4]; 4],
+    pub proj: [[f32; 4]; 4],
+}
+
+// cgmath builds OpenGL projections with a depth between -1 and 1, wgpu expects 0 to 1
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// Never look exactly from above or below, the view matrix is undefined there
+const MAX_PITCH: f32 = 1.55;
+
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,   // around the y axis, in radians
+    pub pitch: f32, // above the horizontal plane, in radians
+    pub fovy: f32,  // in degrees
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl OrbitCamera {
+    // The camera of the scene, looking from `eye` at `target`
+    pub fn new(config: &CameraConfig) -> Self {
+        let offset = Vector3::from(config.eye) - Vector3::from(config.target);
+        let distance = offset.magnitude();
+        Self {
+            target: Point3::from(config.target),
+            distance,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).asin().clamp(-MAX_PITCH, MAX_PITCH),
+            fovy: config.fovy,
+            znear: config.znear,
+            zfar: config.zfar,
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let direction = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + direction * self.distance
+    }
+
+    // Turn around the target, the angles are in radians
+    pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.yaw += delta_yaw;
+        self.pitch = (self.pitch + delta_pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // Move the target in the view plane, the offsets are fractions of the distance so
+    // panning feels the same close to the target and far from it
+    pub fn pan(&mut self, right: f32, up: f32) {
+        self.target += self.view_offset(right, up);
+    }
+
+    // A displacement in the view plane, in fractions of the distance to the target
+    pub fn view_offset(&self, right: f32, up: f32) -> Vector3<f32> {
+        let forward = (self.target - self.eye()).normalize();
+        let right_axis = forward.cross(Vector3::unit_y()).normalize();
+        let up_axis = right_axis.cross(forward);
+        (right_axis * right + up_axis * up) * self.distance
+    }
+
+    // Multiply the distance to the target, < 1 moves closer, without crossing the clip planes
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).clamp(self.znear * 2.0, self.zfar * 0.5);
+    }
+
+    pub fn uniform(&self, aspect: f32) -> CameraUniform {
+        let view = Matrix4::look_at_rh(self.eye(), self.target, Vector3::unit_y());
+        let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Rad::from(cgmath::Deg(self.fovy)), aspect, self.znear, self.zfar);
+        CameraUniform {
+            view: view.into(),
+            proj: proj.into(),
+        }
+    }
+}
+
+// =========================================================================================
+//                                   CAMERA CONTROLS
+// =========================================================================================
+// Left button drag orbits, right button drag pans, the wheel zooms and the arrows pan as long
+// as they are held down. A middle button drag moves the sphere in the view plane instead, the
+// offset is collected with `take_sphere_drag`.
+// =========================================================================================
+
+const ORBIT_SPEED: f32 = 0.005; // radians per pixel
+const MOUSE_PAN_SPEED: f32 = 0.002; // fraction of the distance per pixel
+const KEYBOARD_PAN_SPEED: f32 = 0.5; // fraction of the distance per second
+const ZOOM_SPEED: f32 = 0.9; // distance factor per line scrolled
+
+#[derive(Default)]
+pub struct OrbitControls {
+    position: Option<(f64, f64)>, // last position of the mouse
+    orbiting: bool,
+    panning: bool,
+    dragging_sphere: bool,
+    pan_keys: [bool; 4], // left, right, up and down arrows held down
+    sphere_drag: Option<Vector3<f32>>, // dragged since the last `take_sphere_drag`
+}
+
+impl OrbitControls {
+    // Move `camera` with the event, returns false when the event is not a camera control
+    pub fn input(&mut self, camera: &mut OrbitCamera, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match key {
+                    VirtualKeyCode::Left => self.pan_keys[0] = pressed,
+                    VirtualKeyCode::Right => self.pan_keys[1] = pressed,
+                    VirtualKeyCode::Up => self.pan_keys[2] = pressed,
+                    VirtualKeyCode::Down => self.pan_keys[3] = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.orbiting = pressed,
+                    MouseButton::Right => self.panning = pressed,
+                    MouseButton::Middle => self.dragging_sphere = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some((last_x, last_y)) = self.position {
+                    let (dx, dy) = ((position.x - last_x) as f32, (position.y - last_y) as f32);
+                    if self.orbiting {
+                        camera.orbit(-dx * ORBIT_SPEED, dy * ORBIT_SPEED);
+                    }
+                    if self.panning {
+                        camera.pan(-dx * MOUSE_PAN_SPEED, dy * MOUSE_PAN_SPEED);
+                    }
+                    if self.dragging_sphere {
+                        // the sphere moves with the cursor in the view plane
+                        let offset = camera.view_offset(dx * MOUSE_PAN_SPEED, -dy * MOUSE_PAN_SPEED);
+                        self.sphere_drag = Some(self.sphere_drag.unwrap_or(Vector3::new(0.0, 0.0, 0.0)) + offset);
+                    }
+                }
+                self.position = Some((position.x, position.y));
+                self.orbiting || self.panning || self.dragging_sphere
+            }
+            // Scrolling up moves closer to the target
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                };
+                camera.zoom(ZOOM_SPEED.powf(lines));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Keyboard panning for a frame of `delta_time` seconds, in fractions of the distance to
+    // the target per second
+    pub fn update(&self, camera: &mut OrbitCamera, delta_time: f32) {
+        let [left, right, up, down] = self.pan_keys.map(|held| held as i32 as f32);
+        if left + right + up + down > 0.0 {
+            let step = KEYBOARD_PAN_SPEED * delta_time;
+            camera.pan((right - left) * step, (up - down) * step);
+        }
+    }
+
+    // The offset the sphere was dragged by since the last call, None when it was not dragged
+    pub fn take_sphere_drag(&mut self) -> Option<Vector3<f32>> {
+        self.sphere_drag.take()
+    }
+}
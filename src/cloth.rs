@@ -0,0 +1,247 @@
+use std::fmt;
+
+use wgpu_bootstrap::{
+    wgpu,
+    default::Vertex,
+};
+
+use crate::scene::{ClothEdge, Scene};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ComputeData {
+    pub delta_time: f32,
+    pub nb_vertices: u32,
+    pub vertex_mass: f32,
+    pub nb_colliders: u32,
+    pub friction: f32,    // Coulomb coefficient of the colliders without their own friction
+    pub restitution: f32, // fraction of the speed along the normal kept after a collision
+    pub damping: f32,     // xpbd: fraction of the velocity removed per second
+    pub cg_tolerance: f32, // implicit: relative residual at which the conjugate gradient stops
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Velocity {
+    pub velocity: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Force {
+    pub force: [f32; 3],
+}
+
+impl ComputeData {
+    // Simulation parameters uploaded to the compute shaders, delta_time is set every frame.
+    // The stiffness and the damping are stored in every spring, the colliders in their own
+    // storage buffer: only their number is given, by the caller that owns them.
+    pub fn new(scene: &Scene, nb_colliders: u32) -> Self {
+        Self {
+            delta_time: 0.01,
+            nb_vertices: scene.nb_cloth_vertices(),
+            vertex_mass: scene.physics.vertex_mass,
+            nb_colliders,
+            friction: scene.physics.friction,
+            restitution: scene.physics.restitution,
+            damping: scene.physics.damping,
+            cg_tolerance: scene.physics.cg_tolerance,
+        }
+    }
+}
+
+// Create the vertices of the cloth, a flat width x height grid centered on the cloth center.
+// Vertex (i, j) is the i-th along the width (x) and the j-th along the height (z),
+// its index is i * resolution[1] + j.
+pub fn create_cloth_vertices(scene: &Scene) -> Vec<Vertex> {
+    let [nx, nz] = scene.cloth.resolution;
+    let [step_x, step_z] = scene.cloth.cell_size();
+    let cloth_center = scene.cloth.center;
+    let mut cloth_vertices = Vec::new();
+
+    for i in 0..nx {
+        for j in 0..nz {
+            cloth_vertices.push(Vertex {
+                position: [
+                    cloth_center[0] + i as f32 * step_x - (scene.cloth.width / 2.0),
+                    cloth_center[1],
+                    cloth_center[2] + j as f32 * step_z - (scene.cloth.height / 2.0),
+                ],
+                normal: [0.0, 0.0, 0.0],
+                tangent: [0.0, 0.0, 0.0],
+                tex_coords: [
+                    i as f32 * (1.0 / (nx - 1) as f32),
+                    j as f32 * (1.0 / (nz - 1) as f32),
+                ],
+            });
+        }
+    }
+    cloth_vertices
+}
+
+// =========================================================================================
+//                                    INDEX BUFFERS
+// =========================================================================================
+// 16-bit indices can only address 65536 vertices, above that they silently wrap around and
+// the triangles connect random vertices. The indices are built as u32 and only narrowed to
+// u16 when every vertex fits, so small meshes keep the smaller buffer.
+// =========================================================================================
+
+#[derive(Clone, Debug)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+// An index pointing past the last vertex of the mesh
+#[derive(Debug)]
+pub struct IndexError {
+    pub index: u32,
+    pub nb_vertices: u32,
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index {} is out of range for a mesh of {} vertices", self.index, self.nb_vertices)
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+impl Indices {
+    // Check the indices against the vertex count and pick Uint32 when it exceeds 65535
+    pub fn new(indices: Vec<u32>, nb_vertices: u32) -> Result<Self, IndexError> {
+        if let Some(&index) = indices.iter().find(|&&index| index >= nb_vertices) {
+            return Err(IndexError { index, nb_vertices });
+        }
+        if nb_vertices > u16::MAX as u32 {
+            Ok(Indices::U32(indices))
+        } else {
+            // every index is below nb_vertices, so none of them is truncated
+            Ok(Indices::U16(indices.into_iter().map(|index| index as u16).collect()))
+        }
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The raw content of the index buffer
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => bytemuck::cast_slice(indices),
+            Indices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().map(|&index| index as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        }
+    }
+}
+
+// Create the indices of the cloth, two triangles per cell of the grid
+pub fn create_cloth_indices(scene: &Scene) -> Indices {
+    let [nx, nz] = scene.cloth.resolution;
+    let index = |i: u32, j: u32| i * nz + j;
+    let mut cloth_indices: Vec<u32> = Vec::new();
+
+    for i in 0..nx - 1 {
+        for j in 0..nz - 1 {
+            // first triangle
+            cloth_indices.push(index(i, j));
+            cloth_indices.push(index(i, j + 1));
+            cloth_indices.push(index(i + 1, j));
+            // second triangle
+            cloth_indices.push(index(i, j + 1));
+            cloth_indices.push(index(i + 1, j + 1));
+            cloth_indices.push(index(i + 1, j));
+        }
+    }
+    // the scene validation guarantees that the vertex count fits in an u32
+    Indices::new(cloth_indices, scene.nb_cloth_vertices()).expect("the grid indices are always in range")
+}
+
+// Indices of the vertices pinned by the scene: the listed grid vertices and every vertex of
+// the pinned edges, without duplicates
+pub fn pinned_vertices(scene: &Scene) -> Vec<u32> {
+    let [nx, nz] = scene.cloth.resolution;
+    let index = |i: u32, j: u32| i * nz + j;
+    let mut pinned: Vec<u32> = scene.cloth.pins.iter().map(|pin| index(pin[0], pin[1])).collect();
+
+    for edge in &scene.cloth.pinned_edges {
+        match edge {
+            ClothEdge::XMin => pinned.extend((0..nz).map(|j| index(0, j))),
+            ClothEdge::XMax => pinned.extend((0..nz).map(|j| index(nx - 1, j))),
+            ClothEdge::ZMin => pinned.extend((0..nx).map(|i| index(i, 0))),
+            ClothEdge::ZMax => pinned.extend((0..nx).map(|i| index(i, nz - 1))),
+        }
+    }
+    pinned.sort();
+    pinned.dedup();
+    pinned
+}
+
+// 1 / mass of every vertex, 0 for the pinned ones so that no force can move them
+pub fn create_inverse_masses(scene: &Scene) -> Vec<f32> {
+    let mut inverse_masses = vec![1.0 / scene.physics.vertex_mass; scene.nb_cloth_vertices() as usize];
+    for vertex in pinned_vertices(scene) {
+        inverse_masses[vertex as usize] = 0.0;
+    }
+    inverse_masses
+}
+
+// The cloth starts at rest
+pub fn create_velocities(nb_vertices: u32) -> Vec<Velocity> {
+    vec![Velocity { velocity: [0.0, 0.0, 0.0] }; nb_vertices as usize]
+}
+
+// Forces accumulated by forces.wgsl and applied by compute.wgsl, overwritten every iteration
+pub fn create_forces(nb_vertices: u32) -> Vec<Force> {
+    vec![Force { force: [0.0, 0.0, 0.0] }; nb_vertices as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_to_65535_vertices_use_16_bit_indices() {
+        let indices = Indices::new(vec![0, 1, 65534], 65535).unwrap();
+        assert!(matches!(indices, Indices::U16(_)));
+        assert_eq!(indices.format(), wgpu::IndexFormat::Uint16);
+        assert_eq!(indices.to_u32(), [0, 1, 65534]);
+    }
+
+    #[test]
+    fn above_65535_vertices_use_32_bit_indices() {
+        let indices = Indices::new(vec![0, 1, 65535], 65536).unwrap();
+        assert!(matches!(indices, Indices::U32(_)));
+        assert_eq!(indices.format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(indices.to_u32(), [0, 1, 65535]);
+    }
+
+    #[test]
+    fn index_past_the_last_vertex_is_rejected() {
+        let err = Indices::new(vec![0, 1, 3, 2], 3).expect_err("index 3 is out of range");
+        assert_eq!((err.index, err.nb_vertices), (3, 3));
+        // not truncated to 16 bits either
+        assert!(Indices::new(vec![65536], 100).is_err());
+    }
+}
@@ -23,6 +23,7 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>, // Position in clip space for rasterization
     @location(0) tex_coords: vec2<f32>,          // Pass through texture coordinates to the fragment shader
     @location(1) normal: vec3<f32>,              // Pass through the normal vector for lighting calculations
+    @location(2) world_position: vec3<f32>,      // Position used for the specular highlight
 }
 
 // The vertex shader entry point
@@ -35,6 +36,7 @@ fn vs_main(
     // Transform the vertex position from object space to clip space using the view and projection matrices
     out.clip_position = matrices.proj * matrices.view * vec4<f32>(model.position, 1.0);
     out.normal = model.normal; // Pass the normal vector to the output
+    out.world_position = model.position; // The cloth vertices are already in world space
     return out; // Return the output to the rasterizer
 }
 
@@ -46,9 +48,57 @@ var t_diffuse: texture_2d<f32>; // 2D texture for the diffuse color
 @group(0) @binding(1)
 var s_diffuse: sampler;         // Sampler for the texture
 
+// Directional light, ambient term and Blinn-Phong highlight (see light.rs)
+struct Light {
+    direction_x: f32, // Direction in which the light travels (normalized)
+    direction_y: f32,
+    direction_z: f32,
+    color_r: f32,     // Color of the light
+    color_g: f32,
+    color_b: f32,
+    ambient: f32,     // Light received by every face, even the ones facing away
+    specular: f32,    // Strength of the highlight, 0 disables it
+    shininess: f32,   // Exponent of the highlight, higher is sharper
+    two_sided: u32,   // 1 to light the back faces with the flipped normal
+    camera_x: f32,    // Position of the camera, for the highlight
+    camera_y: f32,
+    camera_z: f32,
+}
+
+// Binding the light uniform to group 2, binding 0
+@group(2) @binding(0)
+var<uniform> light: Light;
+
 // The fragment shader entry point
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
     // Sample the diffuse texture using the interpolated texture coordinates
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    // The interpolated normal is not unit length anymore, and can be zero on degenerate vertices
+    var normal = vec3<f32>(0.0, 0.0, 0.0);
+    if (length(in.normal) > 0.0) {
+        normal = normalize(in.normal);
+    }
+    // The back of the cloth faces the other way
+    if (!front_facing && light.two_sided == 1u) {
+        normal = -normal;
+    }
+
+    let light_color = vec3<f32>(light.color_r, light.color_g, light.color_b);
+    let to_light = -vec3<f32>(light.direction_x, light.direction_y, light.direction_z);
+
+    // Lambert diffuse term
+    let diffuse = max(dot(normal, to_light), 0.0);
+
+    // Blinn-Phong specular term, only on the lit side
+    var specular = 0.0;
+    if (light.specular > 0.0 && diffuse > 0.0) {
+        let to_camera = normalize(vec3<f32>(light.camera_x, light.camera_y, light.camera_z) - in.world_position);
+        let halfway = normalize(to_light + to_camera);
+        specular = light.specular * pow(max(dot(normal, halfway), 0.0), light.shininess);
+    }
+
+    let color = base_color.rgb * (light.ambient + diffuse * light_color) + specular * light_color;
+    return vec4<f32>(color, base_color.a);
 }
@@ -0,0 +1,100 @@
+// Render shader of the colliders: spheres, boxes, capsules and planes
+
+// Vertex shader
+
+// Structure containing the view and projection matrices for camera transformations
+struct CameraUniform {
+    view: mat4x4<f32>, // View matrix transforms world coordinates to camera coordinates
+    proj: mat4x4<f32>, // Projection matrix transforms camera coordinates to clip space
+};
+
+// Binding the camera uniform to group 0, binding 0
+@group(0) @binding(0)
+var<uniform> matrices: CameraUniform;
+
+// Model matrix placing the collider mesh, it only moves and rotates it
+struct Model {
+    model: mat4x4<f32>,
+}
+
+// Binding the model uniform to group 2, binding 0
+@group(2) @binding(0)
+var<uniform> transform: Model;
+
+// Structure representing the input to the vertex shader
+struct VertexInput {
+    @location(0) position: vec3<f32>, // Vertex position in the local frame of the collider
+    @location(1) normal: vec3<f32>,   // Vertex normal used for the lighting
+    @location(2) tangent: vec3<f32>,  // Tangent vector for advanced texturing (not used here)
+    @location(3) tex_coords: vec2<f32>, // Texture coordinates for sampling textures (not used here)
+}
+
+// Structure representing the output from the vertex shader
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>, // Position in clip space for rasterization
+    @location(0) normal: vec3<f32>,              // Pass through the normal vector for lighting calculations
+    @location(1) world_position: vec3<f32>,      // Position used for the specular highlight
+}
+
+// The vertex shader entry point
+@vertex
+fn vs_main(
+    model: VertexInput, // Input vertex attributes
+) -> VertexOutput {
+    var out: VertexOutput; // Declare the output variable
+    // Transform the vertex position from object space to clip space.
+    // The collider is drawn with its real size, the collision margin keeps the cloth above it.
+    let world_position = transform.model * vec4<f32>(model.position, 1.0);
+    out.clip_position = matrices.proj * matrices.view * world_position;
+    // There is no scale, the normals are transformed like the directions
+    out.normal = (transform.model * vec4<f32>(model.normal, 0.0)).xyz;
+    out.world_position = world_position.xyz;
+    return out; // Return the transformed position for the rasterizer
+}
+
+// Fragment shader
+
+// Directional light, ambient term and Blinn-Phong highlight (see light.rs)
+struct Light {
+    direction_x: f32, // Direction in which the light travels (normalized)
+    direction_y: f32,
+    direction_z: f32,
+    color_r: f32,     // Color of the light
+    color_g: f32,
+    color_b: f32,
+    ambient: f32,     // Light received by every face, even the ones facing away
+    specular: f32,    // Strength of the highlight, 0 disables it
+    shininess: f32,   // Exponent of the highlight, higher is sharper
+    two_sided: u32,   // Not used, the inside of the colliders is never visible
+    camera_x: f32,    // Position of the camera, for the highlight
+    camera_y: f32,
+    camera_z: f32,
+}
+
+// Binding the light uniform to group 1, binding 0
+@group(1) @binding(0)
+var<uniform> light: Light;
+
+// The fragment shader entry point
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base_color = vec3<f32>(0.0, 0.0, 1.0); // The colliders are blue like the sphere has always been
+    let normal = normalize(in.normal);
+
+    let light_color = vec3<f32>(light.color_r, light.color_g, light.color_b);
+    let to_light = -vec3<f32>(light.direction_x, light.direction_y, light.direction_z);
+
+    // Lambert diffuse term
+    let diffuse = max(dot(normal, to_light), 0.0);
+
+    // Blinn-Phong specular term, only on the lit side
+    var specular = 0.0;
+    if (light.specular > 0.0 && diffuse > 0.0) {
+        let to_camera = normalize(vec3<f32>(light.camera_x, light.camera_y, light.camera_z) - in.world_position);
+        let halfway = normalize(to_light + to_camera);
+        specular = light.specular * pow(max(dot(normal, halfway), 0.0), light.shininess);
+    }
+
+    let color = base_color * (light.ambient + diffuse * light_color) + specular * light_color;
+    return vec4<f32>(color, 1.0);
+}
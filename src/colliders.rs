@@ -0,0 +1,464 @@
+use wgpu_bootstrap::{
+    cgmath::{self, InnerSpace, Matrix3, Matrix4, Vector3},
+    default::Vertex,
+    geometry::icosphere,
+};
+
+use crate::cloth::ComputeData;
+use crate::motion::SphereMotion;
+use crate::scene::{ColliderConfig, ColliderShape, Scene};
+use crate::sdf::{sdf_mesh, SdfAtlas};
+
+// =========================================================================================
+//                                      COLLIDERS
+// =========================================================================================
+// Every obstacle of the scene is a collider in a storage buffer read by compute.wgsl:
+// spheres, boxes, capsules, infinite planes and signed distance fields (see sdf.rs), each
+// with its own position and rotation.
+// A vertex is moved into the local frame of the collider, where every shape is centered
+// on the origin and aligned with the axes, pushed out of the shape there and moved back.
+// The sphere of the [sphere] section is the first collider, the only one that moves, and
+// the ground of the [ground] section is the last one.
+// =========================================================================================
+
+// Values of `Collider::shape`, same as in compute.wgsl
+pub const SHAPE_SPHERE: u32 = 0;
+pub const SHAPE_BOX: u32 = 1;
+pub const SHAPE_CAPSULE: u32 = 2;
+pub const SHAPE_PLANE: u32 = 3;
+pub const SHAPE_SDF: u32 = 4;
+
+// `Collider::friction` of the colliders using the friction of the physics section
+pub const USE_COMPUTE_DATA_FRICTION: f32 = -1.0;
+
+// Same layout as `Collider` in compute.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Collider {
+    pub shape: u32,
+    pub margin: f32,      // distance kept between the cloth and the surface
+    pub friction: f32,    // Coulomb coefficient, negative to use the one of `ComputeData`
+    pub radius: f32,      // sphere and capsule
+    pub half_height: f32, // capsule: half length of its segment, along the local y axis
+    pub center: [f32; 3],
+    pub half_extents: [f32; 3], // box, and the box covered by a signed distance field
+    pub rotation: [f32; 9],     // local x, y and z axes in world space, one after the other
+    pub velocity: [f32; 3],     // the friction carries the vertices touching the collider along
+    pub sdf_offset: u32,         // signed distance field: first z slice in the atlas
+    pub sdf_resolution: [u32; 3], // signed distance field: samples along each axis
+}
+
+impl Collider {
+    pub fn new(config: &ColliderConfig) -> Self {
+        let shape = match config.shape {
+            ColliderShape::Sphere => SHAPE_SPHERE,
+            ColliderShape::Box => SHAPE_BOX,
+            ColliderShape::Capsule => SHAPE_CAPSULE,
+            ColliderShape::Plane => SHAPE_PLANE,
+            ColliderShape::Sdf => SHAPE_SDF,
+        };
+        // the box of a field comes from its grid, its offset in the atlas is set by `Colliders::new`
+        let (half_extents, sdf_resolution) = match config.shape {
+            ColliderShape::Sdf => (config.sdf.half_extents, config.sdf.resolution),
+            _ => (config.half_extents, [0, 0, 0]),
+        };
+        // rotated around x, then y, then z
+        let [rx, ry, rz] = config.rotation;
+        let rotation = Matrix3::from_angle_z(cgmath::Deg(rz)) * Matrix3::from_angle_y(cgmath::Deg(ry)) * Matrix3::from_angle_x(cgmath::Deg(rx));
+        let axes: [[f32; 3]; 3] = rotation.into();
+        Self {
+            shape,
+            margin: config.margin,
+            friction: config.friction.unwrap_or(USE_COMPUTE_DATA_FRICTION),
+            radius: config.radius,
+            half_height: config.half_height,
+            center: config.center,
+            half_extents,
+            rotation: bytemuck::cast(axes),
+            velocity: [0.0, 0.0, 0.0],
+            sdf_offset: 0,
+            sdf_resolution,
+        }
+    }
+
+    // The ground of the [ground] section, a horizontal plane
+    pub fn ground(height: f32, margin: f32, friction: f32) -> Self {
+        Self {
+            shape: SHAPE_PLANE,
+            center: [0.0, height, 0.0],
+            friction,
+            ..Self::sphere([0.0, 0.0, 0.0], 0.0, margin)
+        }
+    }
+
+    // The sphere of the [sphere] section, axis aligned
+    pub fn sphere(center: [f32; 3], radius: f32, margin: f32) -> Self {
+        Self {
+            shape: SHAPE_SPHERE,
+            margin,
+            friction: USE_COMPUTE_DATA_FRICTION,
+            radius,
+            half_height: 0.0,
+            center,
+            half_extents: [0.0, 0.0, 0.0],
+            rotation: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            velocity: [0.0, 0.0, 0.0],
+            sdf_offset: 0,
+            sdf_resolution: [0, 0, 0],
+        }
+    }
+
+    fn axes(&self) -> [Vector3<f32>; 3] {
+        let r = self.rotation;
+        [
+            Vector3::new(r[0], r[1], r[2]),
+            Vector3::new(r[3], r[4], r[5]),
+            Vector3::new(r[6], r[7], r[8]),
+        ]
+    }
+
+    // Into the local frame of the collider
+    fn local_position(&self, position: Vector3<f32>) -> Vector3<f32> {
+        let [axis_x, axis_y, axis_z] = self.axes();
+        let d = position - Vector3::from(self.center);
+        Vector3::new(d.dot(axis_x), d.dot(axis_y), d.dot(axis_z))
+    }
+
+    // Same as `collide` in compute.wgsl: the position pushed out of the collider and its
+    // margin, or None when the position is outside of them
+    pub fn collide(&self, position: Vector3<f32>, atlas: &SdfAtlas) -> Option<Contact> {
+        let center = Vector3::from(self.center);
+        let [axis_x, axis_y, axis_z] = self.axes();
+
+        // into the local frame of the collider
+        let mut local = self.local_position(position);
+        let mut normal = Vector3::unit_y();
+
+        match self.shape {
+            SHAPE_SPHERE | SHAPE_CAPSULE => {
+                // a capsule is a sphere swept along the y axis, push away from the closest
+                // point of its segment
+                let core = Vector3::new(0.0, local.y.clamp(-self.half_height, self.half_height), 0.0);
+                let offset = local - core;
+                let distance = offset.magnitude();
+                let radius = self.radius + self.margin;
+                if distance >= radius {
+                    return None;
+                }
+                if distance > 0.0 {
+                    normal = offset / distance;
+                }
+                local = core + normal * radius;
+            }
+            SHAPE_BOX => {
+                let h = Vector3::from(self.half_extents);
+                let closest = Vector3::new(local.x.clamp(-h.x, h.x), local.y.clamp(-h.y, h.y), local.z.clamp(-h.z, h.z));
+                let offset = local - closest;
+                let distance = offset.magnitude();
+                if distance > 0.0 {
+                    // outside of the box: the margin rounds its edges and corners
+                    if distance >= self.margin {
+                        return None;
+                    }
+                    normal = offset / distance;
+                    local = closest + normal * self.margin;
+                } else {
+                    // inside of the box: leave through the closest face
+                    let depth = Vector3::new(h.x - local.x.abs(), h.y - local.y.abs(), h.z - local.z.abs());
+                    let side = |value: f32| if value >= 0.0 { 1.0 } else { -1.0 };
+                    if depth.x <= depth.y && depth.x <= depth.z {
+                        normal = Vector3::new(side(local.x), 0.0, 0.0);
+                        local.x = side(local.x) * (h.x + self.margin);
+                    } else if depth.y <= depth.z {
+                        normal = Vector3::new(0.0, side(local.y), 0.0);
+                        local.y = side(local.y) * (h.y + self.margin);
+                    } else {
+                        normal = Vector3::new(0.0, 0.0, side(local.z));
+                        local.z = side(local.z) * (h.z + self.margin);
+                    }
+                }
+            }
+            SHAPE_PLANE => {
+                // the cloth stays on the side of the local y axis
+                if local.y >= self.margin {
+                    return None;
+                }
+                local.y = self.margin;
+            }
+            SHAPE_SDF => {
+                // nothing outside of the box of the field
+                let h = Vector3::from(self.half_extents);
+                if local.x.abs() > h.x || local.y.abs() > h.y || local.z.abs() > h.z {
+                    return None;
+                }
+                let distance = self.sdf_distance(local, atlas);
+                if distance >= self.margin {
+                    return None;
+                }
+                let gradient = self.sdf_gradient(local, atlas);
+                if gradient.magnitude2() > 0.0 {
+                    normal = gradient.normalize();
+                }
+                local += normal * (self.margin - distance);
+            }
+            _ => return None,
+        }
+
+        // back into the world
+        Some(Contact {
+            position: center + axis_x * local.x + axis_y * local.y + axis_z * local.z,
+            normal: axis_x * normal.x + axis_y * normal.y + axis_z * normal.z,
+        })
+    }
+
+    // Same as `sdf_distance` in compute.wgsl: trilinear interpolation of the field at a
+    // local position, clamped to its box
+    pub fn sdf_distance(&self, local: Vector3<f32>, atlas: &SdfAtlas) -> f32 {
+        let h = Vector3::from(self.half_extents);
+        let last = self.sdf_resolution.map(|n| (n - 1) as f32);
+        let mut base = [0u32; 3];
+        let mut fraction = [0.0f32; 3];
+        for axis in 0..3 {
+            let g = ((local[axis] + h[axis]) * last[axis] / (2.0 * h[axis])).clamp(0.0, last[axis]);
+            let i = g.floor().min(last[axis] - 1.0);
+            base[axis] = i as u32;
+            fraction[axis] = g - i;
+        }
+        let [x, y, z] = base;
+        let z = z + self.sdf_offset;
+        let [fx, fy, fz] = fraction;
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z: u32| {
+            let bottom = lerp(atlas.load(x, y, z), atlas.load(x + 1, y, z), fx);
+            let top = lerp(atlas.load(x, y + 1, z), atlas.load(x + 1, y + 1, z), fx);
+            lerp(bottom, top, fy)
+        };
+        lerp(plane(z), plane(z + 1), fz)
+    }
+
+    // Same as `sdf_gradient` in compute.wgsl: central differences one cell apart, not normalized
+    pub fn sdf_gradient(&self, local: Vector3<f32>, atlas: &SdfAtlas) -> Vector3<f32> {
+        let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+        for axis in 0..3 {
+            let mut step = Vector3::new(0.0, 0.0, 0.0);
+            step[axis] = 2.0 * self.half_extents[axis] / (self.sdf_resolution[axis] - 1) as f32;
+            gradient[axis] = self.sdf_distance(local + step, atlas) - self.sdf_distance(local - step, atlas);
+        }
+        gradient
+    }
+
+    // Exact signed distance to the surface of a sphere, box, capsule or plane, used to build
+    // the signed distance fields from primitives
+    pub fn signed_distance(&self, position: Vector3<f32>) -> f32 {
+        let local = self.local_position(position);
+        match self.shape {
+            SHAPE_SPHERE | SHAPE_CAPSULE => {
+                let core = Vector3::new(0.0, local.y.clamp(-self.half_height, self.half_height), 0.0);
+                (local - core).magnitude() - self.radius
+            }
+            SHAPE_BOX => {
+                let q = Vector3::new(
+                    local.x.abs() - self.half_extents[0],
+                    local.y.abs() - self.half_extents[1],
+                    local.z.abs() - self.half_extents[2],
+                );
+                let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+                outside + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            _ => local.y,
+        }
+    }
+
+    // The velocity of a vertex after a contact with this collider
+    pub fn response(&self, velocity: Vector3<f32>, normal: Vector3<f32>, data: &ComputeData) -> Vector3<f32> {
+        contact_response(velocity, normal, Vector3::from(self.velocity), self.friction, data)
+    }
+
+    // Object to world matrix of the collider, for the rendering of its mesh
+    pub fn model_matrix(&self) -> [[f32; 4]; 4] {
+        let [axis_x, axis_y, axis_z] = self.axes();
+        let rotation = Matrix4::from(Matrix3::from_cols(axis_x, axis_y, axis_z));
+        (Matrix4::from_translation(self.center.into()) * rotation).into()
+    }
+}
+
+// A position pushed out of a collider, with the normal of the surface in world space
+pub struct Contact {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+// Same as `response` in compute.wgsl: the velocity of a vertex after a contact with a
+// surface moving at `collider_velocity`, a negative friction uses the one of `data`
+pub fn contact_response(
+    velocity: Vector3<f32>,
+    normal: Vector3<f32>,
+    collider_velocity: Vector3<f32>,
+    friction: f32,
+    data: &ComputeData,
+) -> Vector3<f32> {
+    let relative = velocity - collider_velocity;
+
+    // already leaving the surface, the position correction is enough
+    let normal_speed = relative.dot(normal);
+    if normal_speed >= 0.0 {
+        return velocity;
+    }
+
+    // the speed along the normal is reversed and scaled by the restitution
+    let normal_impulse = -(1.0 + data.restitution) * normal_speed;
+    let normal_velocity = normal * (-data.restitution * normal_speed);
+
+    // Coulomb friction: the sliding speed loses at most friction * normal impulse, a
+    // slower vertex sticks to the collider
+    let friction = if friction >= 0.0 { friction } else { data.friction };
+    let tangential = relative - normal * normal_speed;
+    let tangential_speed = tangential.magnitude();
+    let mut scale = 0.0;
+    if tangential_speed > 0.0 {
+        scale = (1.0 - friction * normal_impulse / tangential_speed).max(0.0);
+    }
+    collider_velocity + normal_velocity + tangential * scale
+}
+
+// =========================================================================================
+//                                   COLLIDER SET
+// =========================================================================================
+
+// Half size of the square drawn for an infinite plane
+const PLANE_DRAW_SIZE: f32 = 100.0;
+
+// The colliders of a scene, with the motion of the sphere when it is enabled
+pub struct Colliders {
+    pub colliders: Vec<Collider>,
+    pub sdf_atlas: SdfAtlas, // the signed distance fields of the colliders, in order
+    sphere_motion: Option<SphereMotion>, // moves colliders[0]
+}
+
+impl Colliders {
+    pub fn new(scene: &Scene) -> Self {
+        let mut colliders = Vec::new();
+        let mut sphere_motion = None;
+        if scene.sphere.enabled {
+            // the margin keeps the cloth triangles from cutting through the drawn sphere
+            colliders.push(Collider::sphere(scene.sphere.center, scene.sphere.radius, scene.sphere.margin));
+            sphere_motion = Some(SphereMotion::new(&scene.sphere));
+        }
+        // the signed distance fields are stacked in the atlas in the order of the colliders
+        let mut sdf_offset = 0;
+        for config in &scene.colliders {
+            let mut collider = Collider::new(config);
+            if collider.shape == SHAPE_SDF {
+                collider.sdf_offset = sdf_offset;
+                sdf_offset += collider.sdf_resolution[2];
+            }
+            colliders.push(collider);
+        }
+        let grids: Vec<_> = scene.colliders.iter().filter(|config| config.shape == ColliderShape::Sdf).map(|config| &config.sdf).collect();
+        let sdf_atlas = SdfAtlas::new(&grids);
+        if scene.ground.enabled {
+            colliders.push(Collider::ground(scene.ground.height, scene.ground.margin, scene.ground.friction));
+        }
+        Self { colliders, sdf_atlas, sphere_motion }
+    }
+
+    // Move the sphere to where it is after `delta_time` more seconds
+    pub fn advance(&mut self, delta_time: f32) {
+        if let Some(motion) = &mut self.sphere_motion {
+            let (center, velocity) = motion.advance(delta_time);
+            self.colliders[0].center = center.into();
+            self.colliders[0].velocity = velocity.into();
+        }
+    }
+
+    // Move the sphere by `offset` during the next step, on top of its trajectory
+    pub fn push_sphere(&mut self, offset: [f32; 3]) {
+        if let Some(motion) = &mut self.sphere_motion {
+            motion.push(offset.into());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.colliders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colliders.is_empty()
+    }
+
+    // Contents of the storage buffer, which cannot be empty: one unused collider when there is none
+    pub fn buffer_contents(&self) -> Vec<Collider> {
+        if self.colliders.is_empty() {
+            vec![Collider::sphere([0.0, 0.0, 0.0], 0.0, 0.0)]
+        } else {
+            self.colliders.clone()
+        }
+    }
+}
+
+// The mesh of a collider in its local frame, to be drawn with `Collider::model_matrix`.
+// Planes are drawn as a large square, the margin is not drawn.
+pub fn collider_mesh(collider: &Collider, atlas: &SdfAtlas) -> (Vec<Vertex>, Vec<u32>) {
+    let vertex = |position: Vector3<f32>, normal: Vector3<f32>| Vertex {
+        position: position.into(),
+        normal: normal.into(),
+        tangent: [0.0, 0.0, 0.0],
+        tex_coords: [0.0, 0.0],
+    };
+
+    match collider.shape {
+        SHAPE_SPHERE | SHAPE_CAPSULE => {
+            // a capsule is a sphere whose two halves are moved apart along y, the triangles
+            // crossing the equator become its cylinder and the normals stay correct
+            let (vertices, indices) = icosphere(4);
+            let vertices = vertices
+                .into_iter()
+                .map(|v| {
+                    let normal = Vector3::from(v.position).normalize();
+                    let mut position = normal * collider.radius;
+                    if collider.shape == SHAPE_CAPSULE {
+                        position.y += collider.half_height.copysign(position.y);
+                    }
+                    vertex(position, normal)
+                })
+                .collect();
+            (vertices, indices.into_iter().map(u32::from).collect())
+        }
+        SHAPE_BOX => {
+            // 4 vertices per face for flat normals
+            let h = Vector3::from(collider.half_extents);
+            let mut vertices = Vec::new();
+            let mut indices = Vec::new();
+            for axis in 0..3 {
+                for side in [1.0, -1.0] {
+                    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+                    normal[axis] = side;
+                    // two directions along the face, with u x v = normal
+                    let mut u = Vector3::new(0.0, 0.0, 0.0);
+                    u[(axis + 1) % 3] = 1.0;
+                    let v = normal.cross(u);
+                    let first = vertices.len() as u32;
+                    for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                        let corner = normal + u * a + v * b;
+                        vertices.push(vertex(Vector3::new(corner.x * h.x, corner.y * h.y, corner.z * h.z), normal));
+                    }
+                    indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+                }
+            }
+            (vertices, indices)
+        }
+        SHAPE_SDF => sdf_mesh(collider, atlas),
+        _ => {
+            // square in the local xz plane, seen from +y
+            let s = PLANE_DRAW_SIZE;
+            let up = Vector3::unit_y();
+            let vertices = vec![
+                vertex(Vector3::new(-s, 0.0, -s), up),
+                vertex(Vector3::new(-s, 0.0, s), up),
+                vertex(Vector3::new(s, 0.0, s), up),
+                vertex(Vector3::new(s, 0.0, -s), up),
+            ];
+            (vertices, vec![0, 1, 2, 0, 2, 3])
+        }
+    }
+}
@@ -0,0 +1,386 @@
+// Collisions of the cloth vertices with the colliders of the scene, shared by the
+// integration passes of every solver: the source of this file is appended to compute.wgsl
+// and xpbd.wgsl (see layout.rs). The including shader declares the vertex positions and
+// velocities, and the simulation parameters `data`.
+
+// Values of Collider.shape (see colliders.rs)
+const SHAPE_SPHERE: u32 = 0u;
+const SHAPE_BOX: u32 = 1u;
+const SHAPE_CAPSULE: u32 = 2u;
+const SHAPE_PLANE: u32 = 3u;
+const SHAPE_SDF: u32 = 4u;
+
+// An obstacle of the scene, every shape is centered on the origin of its local frame
+struct Collider {
+    shape: u32,               // Sphere, box, capsule, plane or signed distance field
+    margin: f32,              // Distance kept between the cloth and the surface
+    friction: f32,            // Coulomb friction coefficient, negative to use data.friction
+    radius: f32,              // Radius of the sphere or of the capsule
+    half_height: f32,         // Half length of the capsule segment, along the local y axis
+    center_x: f32,            // Position of the local frame
+    center_y: f32,
+    center_z: f32,
+    half_extent_x: f32,       // Half size of the box along its local axes, or of the box of the field
+    half_extent_y: f32,
+    half_extent_z: f32,
+    rotation: array<f32, 9>,  // Local x, y and z axes in world space, one after the other
+    velocity_x: f32,          // Velocity of the collider, the friction carries the cloth along
+    velocity_y: f32,
+    velocity_z: f32,
+    sdf_offset: u32,          // First z slice of the signed distance field in sdfAtlas
+    sdf_resolution_x: u32,    // Samples of the field along each axis
+    sdf_resolution_y: u32,
+    sdf_resolution_z: u32,
+}
+
+// A triangle of a mesh collider, in world space (see mesh.rs)
+struct MeshTriangle {
+    a_x: f32,                 // First corner
+    a_y: f32,
+    a_z: f32,
+    b_x: f32,                 // Second corner
+    b_y: f32,
+    b_z: f32,
+    c_x: f32,                 // Third corner
+    c_y: f32,
+    c_z: f32,
+    margin: f32,              // Distance kept between the cloth and the triangle
+    friction: f32,            // Coulomb friction coefficient, negative to use data.friction
+}
+
+// Uniform grid of the mesh triangles, built by mesh_grid.wgsl
+struct MeshGrid {
+    origin_x: f32,            // Corner of the first cell
+    origin_y: f32,
+    origin_z: f32,
+    inverse_cell_size: f32,   // 1 / size of the cubic cells
+    cells_x: u32,             // Number of cells along each axis
+    cells_y: u32,
+    cells_z: u32,
+    nb_cells: u32,
+    nb_triangles: u32,        // 0 when there is no mesh collider
+}
+
+// Result of the collision of a position with a collider
+struct Contact {
+    hit: bool,                // The position was inside the collider or its margin
+    position: vec3<f32>,      // The position pushed out of the collider
+    normal: vec3<f32>,        // Normal of the surface at the contact, in world space
+    friction: f32,            // Friction of the surface, negative to use data.friction
+}
+
+// Buffers and data bindings of the colliders
+@group(2) @binding(1) var<storage, read> colliders: array<Collider>; // Obstacles of the scene
+@group(2) @binding(2) var sdfAtlas: texture_3d<f32>; // Signed distance fields of the colliders, stacked along z
+@group(3) @binding(0) var<uniform> grid: MeshGrid; // Grid of the mesh colliders
+@group(3) @binding(1) var<storage, read> meshTriangles: array<MeshTriangle>; // Triangles of the mesh colliders
+@group(3) @binding(2) var<storage, read> cellOffsets: array<u32>; // The triangles of cell i are cellTriangles[cellOffsets[i]..cellOffsets[i + 1]]
+@group(3) @binding(3) var<storage, read> cellTriangles: array<u32>; // Triangles of every cell
+
+// Push a position out of a collider: the position is moved in the local frame of the
+// collider, pushed out of the shape there and moved back (same as Collider::collide)
+fn collide(world_position: vec3<f32>, collider: Collider) -> Contact {
+    let center = vec3<f32>(collider.center_x, collider.center_y, collider.center_z);
+    let axis_x = vec3<f32>(collider.rotation[0], collider.rotation[1], collider.rotation[2]);
+    let axis_y = vec3<f32>(collider.rotation[3], collider.rotation[4], collider.rotation[5]);
+    let axis_z = vec3<f32>(collider.rotation[6], collider.rotation[7], collider.rotation[8]);
+
+    // Into the local frame of the collider
+    let d = world_position - center;
+    var local = vec3<f32>(dot(d, axis_x), dot(d, axis_y), dot(d, axis_z));
+    var contact: Contact;
+    contact.hit = false;
+    var normal = vec3<f32>(0.0, 1.0, 0.0);
+
+    if (collider.shape == SHAPE_SPHERE || collider.shape == SHAPE_CAPSULE) {
+        // A capsule is a sphere swept along the y axis, push away from the closest point of its segment
+        let core = vec3<f32>(0.0, clamp(local.y, -collider.half_height, collider.half_height), 0.0);
+        let offset = local - core;
+        let distance = length(offset);
+        let radius = collider.radius + collider.margin;
+        if (distance < radius) {
+            if (distance > 0.0) {
+                normal = offset / distance;
+            }
+            local = core + normal * radius;
+            contact.hit = true;
+        }
+    } else if (collider.shape == SHAPE_BOX) {
+        let h = vec3<f32>(collider.half_extent_x, collider.half_extent_y, collider.half_extent_z);
+        let closest = clamp(local, -h, h);
+        let offset = local - closest;
+        let distance = length(offset);
+        if (distance > 0.0) {
+            // Outside of the box: the margin rounds its edges and corners
+            if (distance < collider.margin) {
+                normal = offset / distance;
+                local = closest + normal * collider.margin;
+                contact.hit = true;
+            }
+        } else {
+            // Inside of the box: leave through the closest face
+            let depth = h - abs(local);
+            let side = select(vec3<f32>(-1.0), vec3<f32>(1.0), local >= vec3<f32>(0.0));
+            if (depth.x <= depth.y && depth.x <= depth.z) {
+                normal = vec3<f32>(side.x, 0.0, 0.0);
+                local.x = side.x * (h.x + collider.margin);
+            } else if (depth.y <= depth.z) {
+                normal = vec3<f32>(0.0, side.y, 0.0);
+                local.y = side.y * (h.y + collider.margin);
+            } else {
+                normal = vec3<f32>(0.0, 0.0, side.z);
+                local.z = side.z * (h.z + collider.margin);
+            }
+            contact.hit = true;
+        }
+    } else if (collider.shape == SHAPE_PLANE) {
+        // The cloth stays on the side of the local y axis
+        if (local.y < collider.margin) {
+            local.y = collider.margin;
+            contact.hit = true;
+        }
+    } else if (collider.shape == SHAPE_SDF) {
+        // Nothing outside of the box of the field, inside the distance is the penetration depth
+        let h = vec3<f32>(collider.half_extent_x, collider.half_extent_y, collider.half_extent_z);
+        if (all(abs(local) <= h)) {
+            let distance = sdf_distance(local, collider);
+            if (distance < collider.margin) {
+                let gradient = sdf_gradient(local, collider);
+                if (dot(gradient, gradient) > 0.0) {
+                    normal = normalize(gradient);
+                }
+                local += normal * (collider.margin - distance);
+                contact.hit = true;
+            }
+        }
+    }
+
+    // Back into the world
+    contact.position = center + axis_x * local.x + axis_y * local.y + axis_z * local.z;
+    contact.normal = axis_x * normal.x + axis_y * normal.y + axis_z * normal.z;
+    contact.friction = collider.friction;
+    return contact;
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    return a + (b - a) * t;
+}
+
+// Trilinear interpolation of the signed distance field of a collider at a local position,
+// clamped to the box of the field (same as Collider::sdf_distance). The texels are loaded
+// and interpolated here so that the CPU solver finds the same distances.
+fn sdf_distance(local: vec3<f32>, collider: Collider) -> f32 {
+    let h = vec3<f32>(collider.half_extent_x, collider.half_extent_y, collider.half_extent_z);
+    let resolution = vec3<u32>(collider.sdf_resolution_x, collider.sdf_resolution_y, collider.sdf_resolution_z);
+    let last = vec3<f32>(resolution - vec3<u32>(1u));
+    let g = clamp((local + h) * last / (2.0 * h), vec3<f32>(0.0), last);
+    let i = min(floor(g), last - vec3<f32>(1.0));
+    let f = g - i;
+    let base = vec3<i32>(i) + vec3<i32>(0, 0, i32(collider.sdf_offset));
+
+    var planes: array<f32, 2>;
+    for (var dz = 0; dz < 2; dz++) {
+        let v00 = textureLoad(sdfAtlas, base + vec3<i32>(0, 0, dz), 0).x;
+        let v10 = textureLoad(sdfAtlas, base + vec3<i32>(1, 0, dz), 0).x;
+        let v01 = textureLoad(sdfAtlas, base + vec3<i32>(0, 1, dz), 0).x;
+        let v11 = textureLoad(sdfAtlas, base + vec3<i32>(1, 1, dz), 0).x;
+        planes[dz] = lerp(lerp(v00, v10, f.x), lerp(v01, v11, f.x), f.y);
+    }
+    return lerp(planes[0], planes[1], f.z);
+}
+
+// Gradient of the field by central differences one cell apart, not normalized
+// (same as Collider::sdf_gradient)
+fn sdf_gradient(local: vec3<f32>, collider: Collider) -> vec3<f32> {
+    let h = vec3<f32>(collider.half_extent_x, collider.half_extent_y, collider.half_extent_z);
+    let resolution = vec3<u32>(collider.sdf_resolution_x, collider.sdf_resolution_y, collider.sdf_resolution_z);
+    let cell = 2.0 * h / vec3<f32>(resolution - vec3<u32>(1u));
+    return vec3<f32>(
+        sdf_distance(local + vec3<f32>(cell.x, 0.0, 0.0), collider) - sdf_distance(local - vec3<f32>(cell.x, 0.0, 0.0), collider),
+        sdf_distance(local + vec3<f32>(0.0, cell.y, 0.0), collider) - sdf_distance(local - vec3<f32>(0.0, cell.y, 0.0), collider),
+        sdf_distance(local + vec3<f32>(0.0, 0.0, cell.z), collider) - sdf_distance(local - vec3<f32>(0.0, 0.0, cell.z), collider),
+    );
+}
+
+// Closest point of a triangle, from "Real-Time Collision Detection" (Ericson), same as
+// closest_point_on_triangle in mesh.rs
+fn closest_point_on_triangle(p: vec3<f32>, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>) -> vec3<f32> {
+    let ab = b - a;
+    let ac = c - a;
+
+    // Vertex region of a
+    let ap = p - a;
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if (d1 <= 0.0 && d2 <= 0.0) {
+        return a;
+    }
+
+    // Vertex region of b
+    let bp = p - b;
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if (d3 >= 0.0 && d4 <= d3) {
+        return b;
+    }
+
+    // Edge region of ab
+    let vc = d1 * d4 - d3 * d2;
+    if (vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0) {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    // Vertex region of c
+    let cp = p - c;
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if (d6 >= 0.0 && d5 <= d6) {
+        return c;
+    }
+
+    // Edge region of ac
+    let vb = d5 * d2 - d1 * d6;
+    if (vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0) {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    // Edge region of bc
+    let va = d3 * d6 - d5 * d4;
+    if (va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0) {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Inside the face
+    let denominator = 1.0 / (va + vb + vc);
+    return a + ab * (vb * denominator) + ac * (vc * denominator);
+}
+
+// Push a position out of the closest mesh triangle of its cell, within the margin of the
+// triangle (same as MeshColliders::collide)
+fn collide_meshes(position: vec3<f32>) -> Contact {
+    var contact: Contact;
+    contact.hit = false;
+    if (grid.nb_triangles == 0u) {
+        return contact;
+    }
+
+    // Cell of the position, nothing to do outside of the grid
+    let origin = vec3<f32>(grid.origin_x, grid.origin_y, grid.origin_z);
+    let cell_coordinates = vec3<i32>(floor((position - origin) * grid.inverse_cell_size));
+    let dims = vec3<i32>(i32(grid.cells_x), i32(grid.cells_y), i32(grid.cells_z));
+    if (any(cell_coordinates < vec3<i32>(0)) || any(cell_coordinates >= dims)) {
+        return contact;
+    }
+    let cell = u32((cell_coordinates.z * dims.y + cell_coordinates.y) * dims.x + cell_coordinates.x);
+
+    // Closest triangle, the smallest index wins the ties so the order of the cell does not matter
+    var best_distance = 0.0;
+    var best_triangle = 0u;
+    var best_closest = vec3<f32>(0.0);
+    for (var i = cellOffsets[cell]; i < cellOffsets[cell + 1u]; i++) {
+        let t = cellTriangles[i];
+        let triangle = meshTriangles[t];
+        let a = vec3<f32>(triangle.a_x, triangle.a_y, triangle.a_z);
+        let b = vec3<f32>(triangle.b_x, triangle.b_y, triangle.b_z);
+        let c = vec3<f32>(triangle.c_x, triangle.c_y, triangle.c_z);
+        let closest = closest_point_on_triangle(position, a, b, c);
+        let distance = length(position - closest);
+        let closer = !contact.hit || distance < best_distance || (distance == best_distance && t < best_triangle);
+        if (distance < triangle.margin && closer) {
+            contact.hit = true;
+            best_distance = distance;
+            best_triangle = t;
+            best_closest = closest;
+        }
+    }
+    if (!contact.hit) {
+        return contact;
+    }
+
+    // On the front side the vertex moves away from the closest point, behind the triangle
+    // it is moved to the front side
+    let triangle = meshTriangles[best_triangle];
+    let a = vec3<f32>(triangle.a_x, triangle.a_y, triangle.a_z);
+    let b = vec3<f32>(triangle.b_x, triangle.b_y, triangle.b_z);
+    let c = vec3<f32>(triangle.c_x, triangle.c_y, triangle.c_z);
+    let face_normal = normalize(cross(b - a, c - a));
+    let offset = position - best_closest;
+    var normal = face_normal;
+    if (best_distance > 0.0 && dot(offset, face_normal) >= 0.0) {
+        normal = offset / best_distance;
+    }
+    contact.position = best_closest + normal * triangle.margin;
+    contact.normal = normal;
+    contact.friction = triangle.friction;
+    return contact;
+}
+
+// Velocity of a vertex after a contact, relative to the collider: the normal and the
+// tangential components are handled separately (same as contact_response in colliders.rs)
+fn response(velocity: vec3<f32>, normal: vec3<f32>, collider_velocity: vec3<f32>, collider_friction: f32) -> vec3<f32> {
+    let relative = velocity - collider_velocity;
+
+    // Already leaving the surface, the position correction is enough
+    let normal_speed = dot(relative, normal);
+    if (normal_speed >= 0.0) {
+        return velocity;
+    }
+
+    // The speed along the normal is reversed and scaled by the restitution
+    let normal_impulse = -(1.0 + data.restitution) * normal_speed;
+    let normal_velocity = normal * (-data.restitution * normal_speed);
+
+    // Coulomb friction: the sliding speed loses at most friction * normal impulse, a slower
+    // vertex sticks to the collider
+    var friction = data.friction;
+    if (collider_friction >= 0.0) {
+        friction = collider_friction;
+    }
+    let tangential = relative - normal * normal_speed;
+    let tangential_speed = length(tangential);
+    var scale = 0.0;
+    if (tangential_speed > 0.0) {
+        scale = max(1.0 - friction * normal_impulse / tangential_speed, 0.0);
+    }
+    return collider_velocity + normal_velocity + tangential * scale;
+}
+
+// Move a vertex out of every collider it went into, in order, then out of the mesh
+// colliders, bouncing it and slowing down its sliding each time (same as
+// handle_collisions in cpu.rs)
+fn handle_collisions(index: u32) {
+    for (var i = 0u; i < data.nb_colliders; i++) {
+        let position = vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+        let collider = colliders[i];
+        let contact = collide(position, collider);
+        if (contact.hit) {
+            // Vertex is inside the collider; move it back on its surface
+            verticiesPositions[index].position_x = contact.position.x;
+            verticiesPositions[index].position_y = contact.position.y;
+            verticiesPositions[index].position_z = contact.position.z;
+
+            // Bounce the vertex and slow down its sliding: a moving collider drags the cloth
+            let velocity = vec3<f32>(verticiesVelocities[index].velocity_x, verticiesVelocities[index].velocity_y, verticiesVelocities[index].velocity_z);
+            let collider_velocity = vec3<f32>(collider.velocity_x, collider.velocity_y, collider.velocity_z);
+            let new_velocity = response(velocity, contact.normal, collider_velocity, contact.friction);
+            verticiesVelocities[index].velocity_x = new_velocity.x;
+            verticiesVelocities[index].velocity_y = new_velocity.y;
+            verticiesVelocities[index].velocity_z = new_velocity.z;
+        }
+    }
+
+    // Then with the mesh colliders, which do not move
+    let position = vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+    let contact = collide_meshes(position);
+    if (contact.hit) {
+        verticiesPositions[index].position_x = contact.position.x;
+        verticiesPositions[index].position_y = contact.position.y;
+        verticiesPositions[index].position_z = contact.position.z;
+
+        let velocity = vec3<f32>(verticiesVelocities[index].velocity_x, verticiesVelocities[index].velocity_y, verticiesVelocities[index].velocity_z);
+        let new_velocity = response(velocity, contact.normal, vec3<f32>(0.0), contact.friction);
+        verticiesVelocities[index].velocity_x = new_velocity.x;
+        verticiesVelocities[index].velocity_y = new_velocity.y;
+        verticiesVelocities[index].velocity_z = new_velocity.z;
+    }
+}
@@ -22,46 +22,54 @@ struct Velocity {
     velocity_z: f32, // Velocity along the Z-axis
 }
 
+// Structure to store the force accumulated on each vertex by forces.wgsl
+struct Force {
+    force_x: f32, // Force along the X-axis
+    force_y: f32, // Force along the Y-axis
+    force_z: f32, // Force along the Z-axis
+}
+
 // Uniform data shared across all vertices, including simulation parameters
 struct ComputeData {
     delta_time: f32,          // Time step for the simulation
-    nb_vertices: f32,         // Total number of vertices in the cloth
-    sphere_radius: f32,       // Radius of the collision sphere
-    sphere_center_x: f32,     // X-coordinate of the sphere center
-    sphere_center_y: f32,     // Y-coordinate of the sphere center
-    sphere_center_z: f32,     // Z-coordinate of the sphere center
+    nb_vertices: u32,         // Total number of vertices in the cloth
     vertex_mass: f32,         // Mass of each vertex
-    structural_stiffness: f32, // Stiffness of structural springs
-    shear_stiffness: f32,     // Stiffness of shear springs
-    bend_stiffness: f32,      // Stiffness of bend springs
-    structural_damping: f32,  // Damping of structural springs
-    shear_damping: f32,       // Damping of shear springs
-    bend_damping: f32,        // Damping of bend springs
-}
-
-// Structure to define spring connections between vertices
-struct Spring {
-    vertex_index_1: f32, // Index of the first vertex in the spring
-    vertex_index_2: f32, // Index of the second vertex in the spring
-    rest_length: f32,    // Resting length of the spring
+    nb_colliders: u32,        // Number of colliders used in the colliders buffer
+    friction: f32,            // Coulomb friction coefficient of the colliders without their own
+    restitution: f32,         // Fraction of the speed along the normal kept after a collision
+    damping: f32,             // Fraction of the velocity removed per second by the XPBD solver
+    cg_tolerance: f32,        // Residual at which the conjugate gradient of implicit.wgsl stops
 }
 
 // Buffers and data bindings
 @group(0) @binding(0) var<storage, read_write> verticiesPositions: array<Position>; // Positions of the vertices
 @group(1) @binding(0) var<storage, read_write> verticiesVelocities: array<Velocity>; // Velocities of the vertices
+@group(1) @binding(1) var<storage, read_write> verticiesForces: array<Force>; // Forces computed by forces.wgsl
+@group(1) @binding(2) var<storage, read> inverseMasses: array<f32>; // 1 / mass of the vertices, 0 when pinned
 @group(2) @binding(0) var<uniform> data: ComputeData; // Simulation parameters
-@group(3) @binding(0) var<storage, read> springsR: array<Spring>; // Springs connecting the vertices
 
 // Compute shader entry point
 @compute @workgroup_size(128, 1, 1)
 fn main(@builtin(global_invocation_id) param: vec3<u32>) {
     // Check if the current invocation is within bounds
-    if (param.x >= u32(data.nb_vertices)) {
+    if (param.x >= data.nb_vertices) {
           return;
     }
 
-    // Access the spring data for the current vertex
-    var spring = springsR[param.x];
+    // Pinned vertices have an infinite mass: they stay where they are
+    let inverse_mass = inverseMasses[param.x];
+    if (inverse_mass == 0.0) {
+        verticiesVelocities[param.x].velocity_x = 0.0;
+        verticiesVelocities[param.x].velocity_y = 0.0;
+        verticiesVelocities[param.x].velocity_z = 0.0;
+        return;
+    }
+
+    // Update the velocity of the vertex using the accumulated force
+    // We use for v => v = v + (f / m) * dt
+    verticiesVelocities[param.x].velocity_x += verticiesForces[param.x].force_x * inverse_mass * data.delta_time;
+    verticiesVelocities[param.x].velocity_y += verticiesForces[param.x].force_y * inverse_mass * data.delta_time;
+    verticiesVelocities[param.x].velocity_z += verticiesForces[param.x].force_z * inverse_mass * data.delta_time;
 
     // Update the position of the vertex based on its velocity and delta time
     // We use for x => x = x + v * dt
@@ -69,29 +77,21 @@ fn main(@builtin(global_invocation_id) param: vec3<u32>) {
     verticiesPositions[param.x].position_y += verticiesVelocities[param.x].velocity_y * data.delta_time;
     verticiesPositions[param.x].position_z += verticiesVelocities[param.x].velocity_z * data.delta_time;
 
-    // Calculate the distance from the vertex to the sphere center
-    let sphere_center = vec3<f32>(data.sphere_center_x, data.sphere_center_y, data.sphere_center_z);
-    let sphere_radius = data.sphere_radius;
-    let position = vec3<f32>(verticiesPositions[param.x].position_x, verticiesPositions[param.x].position_y, verticiesPositions[param.x].position_z);
-
-    let distance = length(position - sphere_center);
-
-    // Apply velocity damping (placeholder logic for now)
-    verticiesVelocities[param.x].velocity_x += 0.0;
-
-    // Handle collision detection and response with the sphere
-    if (distance < sphere_radius) {
-        // Vertex is inside the sphere; push it out
-        let normal = normalize(position - sphere_center); // Calculate normal at the collision point
-
-        // Adjust position to be on the sphere's surface
-        verticiesPositions[param.x].position_x += normal.x * (sphere_radius - distance);
-        verticiesPositions[param.x].position_y += normal.y * (sphere_radius - distance);
-        verticiesPositions[param.x].position_z += normal.z * (sphere_radius - distance);
+    // Handle collision detection and response with every collider, see collisions.wgsl
+    handle_collisions(param.x);
+}
 
-        // Stop the velocity to simulate collision response
+// Collisions only, after the Verlet and RK4 integrators of integrators.wgsl
+@compute @workgroup_size(128, 1, 1)
+fn collide_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    if (inverseMasses[param.x] == 0.0) {
         verticiesVelocities[param.x].velocity_x = 0.0;
         verticiesVelocities[param.x].velocity_y = 0.0;
         verticiesVelocities[param.x].velocity_z = 0.0;
+        return;
     }
+    handle_collisions(param.x);
 }
@@ -0,0 +1,470 @@
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    default::Vertex,
+};
+
+use crate::colliders::{contact_response, Colliders};
+use crate::cloth::{ComputeData, Velocity, create_cloth_indices, create_cloth_vertices, create_inverse_masses, create_velocities};
+use crate::implicit::ImplicitSolver;
+use crate::mesh::MeshColliders;
+use crate::normals::{compute_normals, TriangleAdjacency};
+use crate::scene::{Integrator, Scene, Solver};
+use crate::springs::SpringTopology;
+use crate::substeps::{motion_stats, MotionStats, SubstepController};
+use crate::xpbd::{dihedral_angle, XpbdConstraint, XpbdConstraints, CONSTRAINT_BENDING};
+
+// =========================================================================================
+//                                     CPU SOLVER
+// =========================================================================================
+// Pure Rust version of forces.wgsl and compute.wgsl (with implicit.wgsl in between, integrators.wgsl
+// instead of compute.wgsl, or xpbd.wgsl instead of both) working on the same buffers layouts.
+// It is slow but runs everywhere, so it is used as a reference for the shaders and as a
+// fallback when no GPU adapter is available.
+// =========================================================================================
+
+pub const GRAVITY: f32 = 9.81;
+
+pub struct CpuSolver {
+    pub vertices: Vec<Vertex>,
+    pub velocities: Vec<Velocity>,
+    pub inverse_masses: Vec<f32>, // 0 for the pinned vertices
+    pub springs: SpringTopology,
+    pub adjacency: TriangleAdjacency,
+    pub compute_data: ComputeData,
+    pub n_iterations: u32,
+    pub substeps: Option<SubstepController>, // chooses n_iterations at every step when physics.adaptive_iterations is set
+    last_stats: Option<MotionStats>, // adaptive: measured at the start of the last step, like on the GPU
+    pub colliders: Colliders,
+    pub mesh_colliders: MeshColliders,
+    pub xpbd: Option<XpbdConstraints>, // the constraints when physics.solver is xpbd
+    pub implicit: Option<ImplicitSolver>, // the conjugate gradient when physics.solver is implicit
+    pub integrator: Integrator,
+    forces: Vec<Vector3<f32>>,
+    previous_positions: Vec<Vector3<f32>>, // xpbd: positions at the start of the sub-step
+    displacements: Vec<Vector3<f32>>, // verlet: displacement of the last sub-step
+    predicted_positions: Vec<Vector3<f32>>, // verlet: positions at the end of the sub-step, before the collisions
+    rk4_state: Vec<[Vector3<f32>; 4]>, // rk4: x0, v0 and the weighted sums of the derivatives of x and v
+}
+
+impl CpuSolver {
+    pub fn new(scene: &Scene) -> Self {
+        let nb_vertices = scene.nb_cloth_vertices();
+        let indices = create_cloth_indices(scene).to_u32();
+        let adjacency = TriangleAdjacency::new(nb_vertices, &indices);
+        let mut vertices = create_cloth_vertices(scene);
+        compute_normals(&mut vertices, &adjacency);
+        let positions: Vec<Vector3<f32>> = vertices.iter().map(|vertex| Vector3::from(vertex.position)).collect();
+        let springs = SpringTopology::grid(scene);
+        let xpbd = match scene.physics.solver {
+            Solver::Xpbd => Some(XpbdConstraints::new(&springs, &indices, &vertices, &scene.physics)),
+            Solver::MassSpring | Solver::Implicit => None,
+        };
+        let implicit = match scene.physics.solver {
+            Solver::Implicit => Some(ImplicitSolver::new(nb_vertices, scene.physics.cg_iterations)),
+            Solver::MassSpring | Solver::Xpbd => None,
+        };
+        let colliders = Colliders::new(scene);
+        let substeps = scene.physics.adaptive_iterations.then(|| SubstepController::new(scene, &springs));
+        Self {
+            vertices,
+            velocities: create_velocities(nb_vertices),
+            inverse_masses: create_inverse_masses(scene),
+            springs,
+            adjacency,
+            compute_data: ComputeData::new(scene, colliders.len() as u32),
+            n_iterations: substeps.as_ref().map_or(scene.physics.iterations, SubstepController::iterations),
+            substeps,
+            last_stats: None,
+            colliders,
+            mesh_colliders: MeshColliders::new(scene),
+            xpbd,
+            implicit,
+            integrator: scene.physics.integrator,
+            forces: vec![Vector3::new(0.0, 0.0, 0.0); nb_vertices as usize],
+            previous_positions: positions.clone(),
+            displacements: vec![Vector3::new(0.0, 0.0, 0.0); nb_vertices as usize],
+            predicted_positions: positions,
+            rk4_state: vec![[Vector3::new(0.0, 0.0, 0.0); 4]; nb_vertices as usize],
+        }
+    }
+
+    // Advance the simulation by one time step, splitting delta_time like the GPU version
+    pub fn step(&mut self, delta_time: f32) {
+        // Same as the GPU: the count is chosen from the stats of the last step, one step late
+        if let Some(substeps) = &mut self.substeps {
+            if let Some(stats) = self.last_stats {
+                self.n_iterations = substeps.choose(delta_time, stats);
+            }
+            self.last_stats = Some(motion_stats(&self.vertices, &self.velocities, &self.inverse_masses, &self.springs));
+        }
+        self.colliders.advance(delta_time);
+        self.compute_data.delta_time = delta_time / self.n_iterations as f32;
+        for _ in 0..self.n_iterations {
+            self.iterate();
+        }
+        compute_normals(&mut self.vertices, &self.adjacency);
+    }
+
+    // One iteration: the forces pass followed by the integration pass, with the implicit solve
+    // in between for the implicit solver, or the three XPBD passes
+    pub fn iterate(&mut self) {
+        if self.xpbd.is_some() {
+            self.xpbd_predict();
+            self.xpbd_project();
+            self.xpbd_finalize();
+        } else if self.integrator == Integrator::Verlet {
+            self.compute_forces();
+            self.verlet();
+            self.collide();
+        } else if self.integrator == Integrator::Rk4 {
+            self.rk4_begin();
+            for (weight, step) in [(1.0, 0.5), (2.0, 0.5), (2.0, 1.0)] {
+                self.compute_forces();
+                self.rk4_stage(weight, step * self.compute_data.delta_time);
+            }
+            self.compute_forces();
+            self.rk4_finish();
+            self.collide();
+        } else {
+            self.compute_forces();
+            if let Some(implicit) = &mut self.implicit {
+                implicit.solve(&self.springs, &self.vertices, &self.velocities, &self.inverse_masses, &self.compute_data, &mut self.forces);
+            }
+            self.integrate();
+        }
+    }
+
+    // Same as forces.wgsl: accumulate the spring forces and the gravity of every vertex
+    pub fn compute_forces(&mut self) {
+        let data = self.compute_data;
+        let nb_vertices = data.nb_vertices as usize;
+
+        for i in 0..nb_vertices {
+            let mut force_sum = Vector3::new(0.0, 0.0, 0.0);
+
+            let range = self.springs.ranges[i];
+            for spring in &self.springs.springs[range.offset as usize..(range.offset + range.count) as usize] {
+                let vertex_index_1 = spring.index1 as usize;
+                let vertex_index_2 = spring.index2 as usize;
+
+                // Hooke's law along the spring
+                let position_1 = Vector3::from(self.vertices[vertex_index_1].position);
+                let position_2 = Vector3::from(self.vertices[vertex_index_2].position);
+                let distance = (position_1 - position_2).magnitude();
+                let direction = (position_1 - position_2).normalize();
+                force_sum += -spring.stiffness * (distance - spring.rest_length) * direction;
+
+                // Damping against the relative velocity
+                let velocity_1 = Vector3::from(self.velocities[vertex_index_1].velocity);
+                let velocity_2 = Vector3::from(self.velocities[vertex_index_2].velocity);
+                let relative_velocity = (velocity_1 - velocity_2).magnitude();
+                if relative_velocity != 0.0 {
+                    let velocity_direction = (velocity_1 - velocity_2).normalize();
+                    force_sum += -spring.damping * relative_velocity * velocity_direction;
+                }
+            }
+
+            // Gravity
+            force_sum.y += -GRAVITY * data.vertex_mass;
+
+            self.forces[i] = force_sum;
+        }
+    }
+
+    // Same as compute.wgsl: v = v + (f / m) * dt, x = x + v * dt, then push the vertices out of the colliders
+    pub fn integrate(&mut self) {
+        let data = self.compute_data;
+
+        for (i, (vertex, velocity)) in self.vertices.iter_mut().zip(self.velocities.iter_mut()).enumerate() {
+            // Pinned vertices stay where they are
+            let inverse_mass = self.inverse_masses[i];
+            if inverse_mass == 0.0 {
+                velocity.velocity = [0.0, 0.0, 0.0];
+                continue;
+            }
+
+            let force = self.forces[i];
+            velocity.velocity[0] += force.x * inverse_mass * data.delta_time;
+            velocity.velocity[1] += force.y * inverse_mass * data.delta_time;
+            velocity.velocity[2] += force.z * inverse_mass * data.delta_time;
+            for axis in 0..3 {
+                vertex.position[axis] += velocity.velocity[axis] * data.delta_time;
+            }
+
+            handle_collisions(&self.colliders, &self.mesh_colliders, &data, vertex, velocity);
+        }
+    }
+
+    // Same as verlet_main in integrators.wgsl: x(t + h) = x + (x - x(t - h)) + a h^2, with the
+    // displacement x - x(t - h) kept instead of x(t - h)
+    pub fn verlet(&mut self) {
+        let h = self.compute_data.delta_time;
+
+        for (i, (vertex, velocity)) in self.vertices.iter_mut().zip(self.velocities.iter_mut()).enumerate() {
+            let position = Vector3::from(vertex.position);
+            if self.inverse_masses[i] == 0.0 {
+                self.displacements[i] = Vector3::new(0.0, 0.0, 0.0);
+                self.predicted_positions[i] = position;
+                velocity.velocity = [0.0, 0.0, 0.0];
+                continue;
+            }
+
+            // a vertex moved by the collisions gets the displacement matching its new velocity
+            let mut displacement = self.displacements[i];
+            if position != self.predicted_positions[i] {
+                displacement = Vector3::from(velocity.velocity) * h;
+            }
+
+            displacement += self.forces[i] * self.inverse_masses[i] * h * h;
+            let next = position + displacement;
+            self.displacements[i] = displacement;
+            self.predicted_positions[i] = next;
+            vertex.position = next.into();
+            velocity.velocity = (displacement / h).into();
+        }
+    }
+
+    // Same as rk4_begin_main in integrators.wgsl: save the state at the start of the step
+    pub fn rk4_begin(&mut self) {
+        for (i, state) in self.rk4_state.iter_mut().enumerate() {
+            let zero = Vector3::new(0.0, 0.0, 0.0);
+            *state = [Vector3::from(self.vertices[i].position), Vector3::from(self.velocities[i].velocity), zero, zero];
+        }
+    }
+
+    // Same as rk4_stage in integrators.wgsl: add the derivative at the current state with `weight`,
+    // then move to the state `step` seconds after the start of the step along it
+    pub fn rk4_stage(&mut self, weight: f32, step: f32) {
+        for (i, state) in self.rk4_state.iter_mut().enumerate() {
+            if self.inverse_masses[i] == 0.0 {
+                continue;
+            }
+            let dx = Vector3::from(self.velocities[i].velocity);
+            let dv = self.forces[i] * self.inverse_masses[i];
+            state[2] += weight * dx;
+            state[3] += weight * dv;
+            self.vertices[i].position = (state[0] + step * dx).into();
+            self.velocities[i].velocity = (state[1] + step * dv).into();
+        }
+    }
+
+    // Same as rk4_finish_main in integrators.wgsl: x = x0 + h / 6 (k1 + 2 k2 + 2 k3 + k4), same for v
+    pub fn rk4_finish(&mut self) {
+        let h = self.compute_data.delta_time;
+        for (i, state) in self.rk4_state.iter().enumerate() {
+            if self.inverse_masses[i] == 0.0 {
+                continue;
+            }
+            let sum_x = state[2] + Vector3::from(self.velocities[i].velocity);
+            let sum_v = state[3] + self.forces[i] * self.inverse_masses[i];
+            self.vertices[i].position = (state[0] + h / 6.0 * sum_x).into();
+            self.velocities[i].velocity = (state[1] + h / 6.0 * sum_v).into();
+        }
+    }
+
+    // Same as collide_main in compute.wgsl
+    pub fn collide(&mut self) {
+        let data = self.compute_data;
+        for (i, (vertex, velocity)) in self.vertices.iter_mut().zip(self.velocities.iter_mut()).enumerate() {
+            if self.inverse_masses[i] == 0.0 {
+                velocity.velocity = [0.0, 0.0, 0.0];
+                continue;
+            }
+            handle_collisions(&self.colliders, &self.mesh_colliders, &data, vertex, velocity);
+        }
+    }
+
+    // Same as predict_main in xpbd.wgsl: v = v + g * dt, x = x + v * dt
+    pub fn xpbd_predict(&mut self) {
+        let data = self.compute_data;
+
+        for (i, (vertex, velocity)) in self.vertices.iter_mut().zip(self.velocities.iter_mut()).enumerate() {
+            self.previous_positions[i] = Vector3::from(vertex.position);
+            if self.inverse_masses[i] == 0.0 {
+                velocity.velocity = [0.0, 0.0, 0.0];
+                continue;
+            }
+            velocity.velocity[1] += -GRAVITY * data.delta_time;
+            for axis in 0..3 {
+                vertex.position[axis] += velocity.velocity[axis] * data.delta_time;
+            }
+        }
+    }
+
+    // Same as project_main in xpbd.wgsl, one color after the other. The constraints of a color
+    // share no vertex, so solving them in order gives the same result as the GPU.
+    pub fn xpbd_project(&mut self) {
+        let Some(xpbd) = self.xpbd.take() else {
+            return;
+        };
+        let delta_time = self.compute_data.delta_time;
+
+        for color in &xpbd.colors {
+            for constraint in &xpbd.constraints[color.offset as usize..(color.offset + color.count) as usize] {
+                // Compliance scaled by the time step: alpha~ = alpha / dt^2
+                let alpha = constraint.compliance / (delta_time * delta_time);
+                if constraint.kind == CONSTRAINT_BENDING {
+                    self.project_bending(constraint, alpha);
+                } else {
+                    self.project_distance(constraint, alpha);
+                }
+            }
+        }
+        self.xpbd = Some(xpbd);
+    }
+
+    // Same as project_distance in xpbd.wgsl: C = |x1 - x2| - rest, dlambda = -C / (w1 + w2 + alpha~)
+    fn project_distance(&mut self, constraint: &XpbdConstraint, alpha: f32) {
+        let (index1, index2) = (constraint.vertices[0] as usize, constraint.vertices[1] as usize);
+        let w1 = self.inverse_masses[index1];
+        let w2 = self.inverse_masses[index2];
+        if w1 + w2 + alpha == 0.0 {
+            return;
+        }
+
+        let x1 = Vector3::from(self.vertices[index1].position);
+        let x2 = Vector3::from(self.vertices[index2].position);
+        let distance = (x1 - x2).magnitude();
+        if distance < 1e-6 {
+            return;
+        }
+        let n = (x1 - x2) / distance;
+
+        let dlambda = -(distance - constraint.rest) / (w1 + w2 + alpha);
+        self.vertices[index1].position = (x1 + w1 * dlambda * n).into();
+        self.vertices[index2].position = (x2 - w2 * dlambda * n).into();
+    }
+
+    // Same as project_bending in xpbd.wgsl:
+    // C = angle - rest, dlambda = -C / (sum of wi |grad_i C|^2 + alpha~)
+    fn project_bending(&mut self, constraint: &XpbdConstraint, alpha: f32) {
+        let indices = constraint.vertices.map(|vertex| vertex as usize);
+        let positions = indices.map(|index| Vector3::from(self.vertices[index].position));
+        let Some((angle, gradients)) = dihedral_angle(positions) else {
+            return;
+        };
+        let weights = indices.map(|index| self.inverse_masses[index]);
+
+        let denominator = weights.iter().zip(&gradients).map(|(w, d)| w * d.magnitude2()).sum::<f32>() + alpha;
+        if denominator == 0.0 {
+            return;
+        }
+        let dlambda = -(angle - constraint.rest) / denominator;
+        for k in 0..4 {
+            self.vertices[indices[k]].position = (positions[k] + weights[k] * dlambda * gradients[k]).into();
+        }
+    }
+
+    // Same as finalize_main in xpbd.wgsl: v = (x - x_prev) / dt, damping, then the collisions
+    pub fn xpbd_finalize(&mut self) {
+        let data = self.compute_data;
+
+        for (i, (vertex, velocity)) in self.vertices.iter_mut().zip(self.velocities.iter_mut()).enumerate() {
+            if self.inverse_masses[i] == 0.0 {
+                continue;
+            }
+            let damping = (1.0 - data.damping * data.delta_time).max(0.0);
+            velocity.velocity = ((Vector3::from(vertex.position) - self.previous_positions[i]) / data.delta_time * damping).into();
+
+            handle_collisions(&self.colliders, &self.mesh_colliders, &data, vertex, velocity);
+        }
+    }
+}
+
+// Same as handle_collisions in collisions.wgsl: move the vertex back on the surface of every
+// collider it went into, bounce it and slow down its sliding with the friction, in the same
+// order as the GPU
+fn handle_collisions(colliders: &Colliders, mesh_colliders: &MeshColliders, data: &ComputeData, vertex: &mut Vertex, velocity: &mut Velocity) {
+    for collider in &colliders.colliders {
+        if let Some(contact) = collider.collide(Vector3::from(vertex.position), &colliders.sdf_atlas) {
+            vertex.position = contact.position.into();
+            velocity.velocity = collider.response(Vector3::from(velocity.velocity), contact.normal, data).into();
+        }
+    }
+
+    // then with the mesh colliders, which do not move
+    if let Some((contact, friction)) = mesh_colliders.collide(Vector3::from(vertex.position)) {
+        vertex.position = contact.position.into();
+        velocity.velocity = contact_response(Vector3::from(velocity.velocity), contact.normal, Vector3::new(0.0, 0.0, 0.0), friction, data).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::PhysicsConfig;
+
+    // A 1 x 1 cloth of 2 x 2 vertices at y = 5, one sub-step per step and no collider unless
+    // `extra` adds one
+    fn solver(extra: &str) -> CpuSolver {
+        let text = format!(
+            "[cloth]\nwidth = 1.0\nheight = 1.0\nresolution = [2, 2]\ncenter = [0.0, 5.0, 0.0]\n{}\n[physics]\niterations = 1\n",
+            extra
+        );
+        CpuSolver::new(&Scene::parse(&text).expect("the test scene is valid"))
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn free_fall_follows_semi_implicit_euler() {
+        let mut solver = solver("[sphere]\nenabled = false");
+        let dt = 0.01;
+        let start: Vec<Vector3<f32>> = solver.vertices.iter().map(|vertex| Vector3::from(vertex.position)).collect();
+        solver.step(dt);
+
+        // the springs are at rest, only the gravity acts: v = g dt, x = x + v dt
+        let velocity = Vector3::new(0.0, -GRAVITY * dt, 0.0);
+        for (i, vertex) in solver.vertices.iter().enumerate() {
+            assert_close(Vector3::from(solver.velocities[i].velocity), velocity);
+            assert_close(Vector3::from(vertex.position), start[i] + velocity * dt);
+        }
+    }
+
+    #[test]
+    fn stretched_spring_pulls_along_its_edge() {
+        let mut solver = solver("[sphere]\nenabled = false");
+        // vertex 2 is the neighbor of vertex 0 along x, stretch their structural spring by 0.1
+        let stretch = 0.1;
+        solver.vertices[2].position[0] += stretch;
+        solver.compute_forces();
+
+        // the other springs of vertex 0 keep their length, the force is Hooke's law towards
+        // vertex 2 plus the weight
+        let stiffness = PhysicsConfig::default().structural_stiffness;
+        let weight = Vector3::new(0.0, -GRAVITY * solver.compute_data.vertex_mass, 0.0);
+        assert_close(solver.forces[0], Vector3::new(stiffness * stretch, 0.0, 0.0) + weight);
+    }
+
+    #[test]
+    fn vertex_inside_the_sphere_is_pushed_to_its_margin() {
+        // the sphere of radius 10 and margin 0.5 at the origin contains the whole cloth
+        let mut solver = solver("[sphere]\nradius = 10.0\nmargin = 0.5\ncenter = [0.0, 0.0, 0.0]");
+        let before: Vec<Vector3<f32>> = solver.vertices.iter().map(|vertex| Vector3::from(vertex.position)).collect();
+        solver.collide();
+
+        for (vertex, before) in solver.vertices.iter().zip(before) {
+            let position = Vector3::from(vertex.position);
+            assert!((position.magnitude() - 10.5).abs() < 1e-4, "{:?} is not on the margin", position);
+            // pushed out along the radius
+            assert_close(position.normalize(), before.normalize());
+        }
+    }
+
+    #[test]
+    fn pinned_vertex_does_not_move() {
+        let mut solver = solver("pins = [[0, 0]]\n[sphere]\nenabled = false");
+        assert_eq!(solver.inverse_masses[0], 0.0);
+        let pinned = solver.vertices[0].position;
+        let free = solver.vertices[3].position;
+        for _ in 0..10 {
+            solver.step(0.01);
+        }
+
+        assert_eq!(solver.vertices[0].position, pinned);
+        assert_eq!(solver.velocities[0].velocity, [0.0, 0.0, 0.0]);
+        assert!(solver.vertices[3].position[1] < free[1], "the free vertex did not fall");
+    }
+}
@@ -0,0 +1,96 @@
+// Render shader of the ground: a large square at the ground height with a grid drawn in the
+// fragment shader, fading into the background far from the center
+
+// Vertex shader
+
+// Structure containing the view and projection matrices for camera transformations
+struct CameraUniform {
+    view: mat4x4<f32>, // View matrix transforms world coordinates to camera coordinates
+    proj: mat4x4<f32>, // Projection matrix transforms camera coordinates to clip space
+};
+
+// Binding the camera uniform to group 0, binding 0
+@group(0) @binding(0)
+var<uniform> matrices: CameraUniform;
+
+// Structure representing the input to the vertex shader
+struct VertexInput {
+    @location(0) position: vec3<f32>, // Vertex position, already in world space
+    @location(1) normal: vec3<f32>,   // Normal of the ground, pointing up
+    @location(2) tangent: vec3<f32>,  // Tangent vector (not used here)
+    @location(3) tex_coords: vec2<f32>, // Texture coordinates (not used here)
+}
+
+// Structure representing the output from the vertex shader
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>, // Position in clip space for rasterization
+    @location(0) world_position: vec3<f32>,      // Position used for the grid lines
+}
+
+// The vertex shader entry point
+@vertex
+fn vs_main(
+    model: VertexInput, // Input vertex attributes
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = matrices.proj * matrices.view * vec4<f32>(model.position, 1.0);
+    out.world_position = model.position;
+    return out;
+}
+
+// Fragment shader
+
+// Directional light, ambient term and Blinn-Phong highlight (see light.rs)
+struct Light {
+    direction_x: f32, // Direction in which the light travels (normalized)
+    direction_y: f32,
+    direction_z: f32,
+    color_r: f32,     // Color of the light
+    color_g: f32,
+    color_b: f32,
+    ambient: f32,     // Light received by every face, even the ones facing away
+    specular: f32,    // Not used, the ground is matte
+    shininess: f32,   // Not used
+    two_sided: u32,   // Not used, the ground is seen from above
+    camera_x: f32,    // Not used
+    camera_y: f32,
+    camera_z: f32,
+}
+
+// Binding the light uniform to group 1, binding 0
+@group(1) @binding(0)
+var<uniform> light: Light;
+
+const CELL_SIZE: f32 = 5.0;          // Distance between two grid lines
+const MAJOR_EVERY: f32 = 5.0;        // Every 5th line is darker
+const FADE_START: f32 = 100.0;       // Distance from the center where the grid starts to fade
+const FADE_END: f32 = 400.0;         // Distance from the center where only the background is left
+const BACKGROUND: vec3<f32> = vec3<f32>(0.85, 0.85, 0.85); // Clear color of the render pass
+
+// 1 on a line of the given spacing, 0 between the lines, antialiased over one pixel
+fn grid_line(position: vec2<f32>, spacing: f32) -> f32 {
+    let coordinates = position / spacing;
+    let distance = abs(fract(coordinates - 0.5) - 0.5) / fwidth(coordinates);
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+// The fragment shader entry point
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light_color = vec3<f32>(light.color_r, light.color_g, light.color_b);
+    let to_light = -vec3<f32>(light.direction_x, light.direction_y, light.direction_z);
+
+    // Lambert diffuse term of the upward normal
+    let diffuse = max(to_light.y, 0.0);
+    let floor_color = vec3<f32>(0.6, 0.6, 0.6) * (light.ambient + diffuse * light_color);
+
+    // Thin lines every cell, darker ones every few cells
+    let minor = grid_line(in.world_position.xz, CELL_SIZE);
+    let major = grid_line(in.world_position.xz, CELL_SIZE * MAJOR_EVERY);
+    var color = mix(floor_color, floor_color * 0.75, minor);
+    color = mix(color, floor_color * 0.5, major);
+
+    // Fade into the background so the edge of the square is not visible
+    let fade = smoothstep(FADE_START, FADE_END, length(in.world_position.xz));
+    return vec4<f32>(mix(color, BACKGROUND, fade), 1.0);
+}
@@ -0,0 +1,365 @@
+use std::fmt;
+use std::io::Write;
+
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    wgpu,
+    default::Vertex,
+};
+
+use crate::cloth::{create_cloth_indices, Velocity};
+use crate::cpu::{CpuSolver, GRAVITY};
+use crate::scene::{Integrator, Scene};
+use crate::simulation::ClothSimulation;
+use crate::springs::SpringTopology;
+use crate::substeps::SubstepController;
+use crate::timestep::FixedTimestep;
+
+// =========================================================================================
+//                                     HEADLESS MODE
+// =========================================================================================
+// Runs the same compute shaders as the windowed application on an offscreen device,
+// without a window or a surface. A software adapter is used when one is available, so
+// the simulation also runs on CI machines without a display or a GPU.
+// =========================================================================================
+
+// Everything that can go wrong while running a headless simulation
+#[derive(Debug)]
+pub enum HeadlessError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    ReadBack(wgpu::BufferAsyncError),
+    Io(String, std::io::Error),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessError::NoAdapter => write!(f, "no wgpu adapter available"),
+            HeadlessError::RequestDevice(err) => write!(f, "cannot create the wgpu device: {}", err),
+            HeadlessError::ReadBack(err) => write!(f, "cannot read back the cloth vertices: {}", err),
+            HeadlessError::Io(path, err) => write!(f, "cannot write '{}': {}", path, err),
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+// An offscreen device, without any surface attached
+pub struct HeadlessDevice {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub adapter_info: wgpu::AdapterInfo,
+}
+
+impl HeadlessDevice {
+    // Prefer the software (fallback) adapter, use any adapter if there is none
+    pub fn new() -> Result<Self, HeadlessError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+        let adapter = [true, false]
+            .into_iter()
+            .find_map(|force_fallback_adapter| {
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter,
+                    compatible_surface: None,
+                }))
+            })
+            .ok_or(HeadlessError::NoAdapter)?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Headless Device"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+            },
+            None,
+        ))
+        .map_err(HeadlessError::RequestDevice)?;
+
+        Ok(Self {
+            device,
+            queue,
+            adapter_info: adapter.get_info(),
+        })
+    }
+}
+
+// Which solver steps the cloth in headless mode
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Gpu,
+    Cpu,
+}
+
+// What a headless run produced: the final cloth and what happened on the way. Nothing is
+// printed while it runs, the caller prints the report with `Display`.
+pub struct HeadlessReport {
+    pub vertices: Vec<Vertex>,
+    pub solver: String,  // the adapter that ran the compute shaders, or the CPU solver
+    pub fell_back: bool, // the GPU was asked for but there was no adapter at all
+    pub frames: u32,
+    pub delta_time: f32,
+    pub substeps: SubstepReport,
+    pub energy: EnergyReport,
+}
+
+// Run `frames` frames of `delta_time` seconds and return the final cloth vertices.
+// The simulation advances by `physics.time_step` like in the window, without the cap on the
+// steps per frame: nothing is dropped, so the result only depends on the total duration.
+// The GPU backend falls back to the CPU solver when there is no adapter at all.
+pub fn run(scene: &Scene, frames: u32, delta_time: f32, backend: Backend) -> Result<HeadlessReport, HeadlessError> {
+    let mut substeps = SubstepReport::new(scene.physics.time_step);
+    let mut energy = EnergyReport::new(scene.physics.integrator);
+    let gpu = match backend {
+        Backend::Gpu => match HeadlessDevice::new() {
+            Ok(headless) => Some(headless),
+            Err(HeadlessError::NoAdapter) => None,
+            Err(err) => return Err(err),
+        },
+        Backend::Cpu => None,
+    };
+    let (solver, (vertices, velocities)) = match &gpu {
+        Some(headless) => (
+            format!("'{}' ({:?})", headless.adapter_info.name, headless.adapter_info.backend),
+            run_gpu(headless, scene, frames, delta_time, &mut substeps, &mut energy)?,
+        ),
+        None => (
+            String::from("the CPU solver"),
+            run_cpu(scene, frames, delta_time, &mut substeps, &mut energy),
+        ),
+    };
+    energy.record(scene, frames, &vertices, &velocities);
+
+    Ok(HeadlessReport {
+        vertices,
+        solver,
+        fell_back: backend == Backend::Gpu && gpu.is_none(),
+        frames,
+        delta_time,
+        substeps,
+        energy,
+    })
+}
+
+impl fmt::Display for HeadlessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "headless: {} frames of {}s on {}", self.frames, self.delta_time, self.solver)?;
+        writeln!(f, "{}", self.substeps)?;
+        // a short summary so batch jobs can see where the cloth ended
+        let (min_y, max_y) = self
+            .vertices
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| (min.min(v.position[1]), max.max(v.position[1])));
+        writeln!(f, "headless: cloth height between {} and {}", min_y, max_y)?;
+        write!(f, "{}", self.energy)
+    }
+}
+
+// The energy is also sampled before the first frame and every ENERGY_SAMPLE_FRAMES frames,
+// the final state is sampled by `run`
+fn run_gpu(
+    headless: &HeadlessDevice,
+    scene: &Scene,
+    frames: u32,
+    delta_time: f32,
+    substeps: &mut SubstepReport,
+    energy: &mut EnergyReport,
+) -> Result<(Vec<Vertex>, Vec<Velocity>), HeadlessError> {
+    let mut simulation = ClothSimulation::new(&headless.device, scene);
+    let read_back = |simulation: &ClothSimulation| -> Result<(Vec<Vertex>, Vec<Velocity>), HeadlessError> {
+        let vertices = simulation
+            .read_vertices(&headless.device, &headless.queue)
+            .map_err(HeadlessError::ReadBack)?;
+        let velocities = simulation
+            .read_velocities(&headless.device, &headless.queue)
+            .map_err(HeadlessError::ReadBack)?;
+        Ok((vertices, velocities))
+    };
+    let mut timestep = FixedTimestep::new(scene.physics.time_step, u32::MAX);
+    for frame in 0..frames {
+        if frame % ENERGY_SAMPLE_FRAMES == 0 {
+            let (vertices, velocities) = read_back(&simulation)?;
+            energy.record(scene, frame, &vertices, &velocities);
+        }
+        for _ in 0..timestep.advance(delta_time) {
+            simulation.step(&headless.device, &headless.queue, timestep.time_step);
+            substeps.add(simulation.iterations());
+        }
+    }
+    substeps.stiffness_time_step = simulation.substep_controller().map(SubstepController::stiffness_time_step);
+    read_back(&simulation)
+}
+
+fn run_cpu(scene: &Scene, frames: u32, delta_time: f32, substeps: &mut SubstepReport, energy: &mut EnergyReport) -> (Vec<Vertex>, Vec<Velocity>) {
+    let mut solver = CpuSolver::new(scene);
+    let mut timestep = FixedTimestep::new(scene.physics.time_step, u32::MAX);
+    for frame in 0..frames {
+        if frame % ENERGY_SAMPLE_FRAMES == 0 {
+            energy.record(scene, frame, &solver.vertices, &solver.velocities);
+        }
+        for _ in 0..timestep.advance(delta_time) {
+            solver.step(timestep.time_step);
+            substeps.add(solver.n_iterations);
+        }
+    }
+    substeps.stiffness_time_step = solver.substeps.as_ref().map(SubstepController::stiffness_time_step);
+    (solver.vertices, solver.velocities)
+}
+
+// Frames between two samples of the energy of the cloth
+const ENERGY_SAMPLE_FRAMES: u32 = 60;
+
+// The total energy of the cloth at the start, every ENERGY_SAMPLE_FRAMES frames and at the end,
+// to compare the energy drift of the integrators on the same scene. The damping, the friction
+// and the inelastic collisions remove energy too: compare the integrators with the same ones.
+pub struct EnergyReport {
+    pub integrator: Integrator,
+    pub samples: Vec<(u32, [f32; 3])>, // frame, then kinetic, gravitational and elastic energy
+}
+
+impl EnergyReport {
+    pub fn new(integrator: Integrator) -> Self {
+        Self {
+            integrator,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, scene: &Scene, frame: u32, vertices: &[Vertex], velocities: &[Velocity]) {
+        self.samples.push((frame, cloth_energy(scene, vertices, velocities)));
+    }
+}
+
+// Every sample, then the final energy, its drift from the initial one and the largest drift
+impl fmt::Display for EnergyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some((_, initial)), Some((_, [kinetic, gravity, elastic]))) = (self.samples.first(), self.samples.last()) else {
+            return write!(f, "headless: no energy sample");
+        };
+        for (frame, energy) in &self.samples {
+            writeln!(f, "headless: frame {}, energy {} J", frame, energy.iter().sum::<f32>())?;
+        }
+        let initial: f32 = initial.iter().sum();
+        let total = kinetic + gravity + elastic;
+        writeln!(
+            f,
+            "headless: energy {} J (kinetic {}, gravitational {}, elastic {})",
+            total, kinetic, gravity, elastic,
+        )?;
+        // relative to the initial energy, or absolute when it is 0
+        let scale = if initial != 0.0 { initial.abs() } else { 1.0 };
+        let largest = self
+            .samples
+            .iter()
+            .map(|(_, energy)| energy.iter().sum::<f32>() - initial)
+            .fold(0.0f32, |largest, drift| if drift.abs() > largest.abs() { drift } else { largest });
+        write!(
+            f,
+            "headless: {:?} energy drift {} J -> {} J, {:+.3}% at the end, largest {:+.3}%",
+            self.integrator,
+            initial,
+            total,
+            100.0 * (total - initial) / scale,
+            100.0 * largest / scale,
+        )
+    }
+}
+
+// The number of steps run and the sub-steps chosen for them
+pub struct SubstepReport {
+    pub time_step: f32,
+    pub nb_steps: u32,
+    pub nb_iterations: u64,
+    pub min_iterations: u32,
+    pub max_iterations: u32,
+    pub stiffness_time_step: Option<f32>, // of the adaptive sub-steps, None when they are off
+}
+
+impl SubstepReport {
+    pub fn new(time_step: f32) -> Self {
+        Self {
+            time_step,
+            nb_steps: 0,
+            nb_iterations: 0,
+            min_iterations: 0,
+            max_iterations: 0,
+            stiffness_time_step: None,
+        }
+    }
+
+    pub fn add(&mut self, iterations: u32) {
+        self.min_iterations = if self.nb_steps == 0 { iterations } else { self.min_iterations.min(iterations) };
+        self.max_iterations = self.max_iterations.max(iterations);
+        self.nb_steps += 1;
+        self.nb_iterations += iterations as u64;
+    }
+}
+
+impl fmt::Display for SubstepReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "headless: {} steps of {}s", self.nb_steps, self.time_step)?;
+        if let (Some(stiffness_time_step), true) = (self.stiffness_time_step, self.nb_steps > 0) {
+            // the XPBD and implicit solvers are only limited by the speed and the strain
+            let bound = if stiffness_time_step.is_finite() {
+                format!("stable sub-step of the springs {}s", stiffness_time_step)
+            } else {
+                String::from("no stiffness limit")
+            };
+            write!(
+                f,
+                "\nheadless: adaptive sub-steps between {} and {}, {:.1} on average ({})",
+                self.min_iterations,
+                self.max_iterations,
+                self.nb_iterations as f64 / self.nb_steps as f64,
+                bound,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Kinetic, gravitational (from y = 0) and elastic energy of the cloth, to compare the energy
+// drift of the integrators on the same scene
+pub fn cloth_energy(scene: &Scene, vertices: &[Vertex], velocities: &[Velocity]) -> [f32; 3] {
+    let mass = scene.physics.vertex_mass;
+    let kinetic = velocities.iter().map(|v| 0.5 * mass * Vector3::from(v.velocity).magnitude2()).sum();
+    let gravity = vertices.iter().map(|v| mass * GRAVITY * v.position[1]).sum();
+    // every spring is stored once for each of its vertices
+    let springs = SpringTopology::grid(scene);
+    let elastic = springs
+        .springs
+        .iter()
+        .filter(|spring| spring.index1 < spring.index2)
+        .map(|spring| {
+            let length = (Vector3::from(vertices[spring.index1 as usize].position) - Vector3::from(vertices[spring.index2 as usize].position)).magnitude();
+            0.5 * spring.stiffness * (length - spring.rest_length).powi(2)
+        })
+        .sum();
+    [kinetic, gravity, elastic]
+}
+
+// Save the cloth as a Wavefront OBJ file
+pub fn write_obj(path: &str, scene: &Scene, vertices: &[Vertex]) -> Result<(), HeadlessError> {
+    let io_error = |err| HeadlessError::Io(path.to_string(), err);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path).map_err(io_error)?);
+    for vertex in vertices {
+        let [x, y, z] = vertex.position;
+        writeln!(file, "v {} {} {}", x, y, z).map_err(io_error)?;
+    }
+    for vertex in vertices {
+        let [u, v] = vertex.tex_coords;
+        writeln!(file, "vt {} {}", u, v).map_err(io_error)?;
+    }
+    for vertex in vertices {
+        let [x, y, z] = vertex.normal;
+        writeln!(file, "vn {} {} {}", x, y, z).map_err(io_error)?;
+    }
+    for triangle in create_cloth_indices(scene).to_u32().chunks(3) {
+        // OBJ indices start at 1
+        let (a, b, c) = (triangle[0] + 1, triangle[1] + 1, triangle[2] + 1);
+        writeln!(file, "f {}/{}/{} {}/{}/{} {}/{}/{}", a, a, a, b, b, b, c, c, c).map_err(io_error)?;
+    }
+    file.flush().map_err(io_error)
+}
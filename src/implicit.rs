@@ -0,0 +1,161 @@
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Matrix3, SquareMatrix, Vector3},
+    default::Vertex,
+};
+
+use crate::cloth::{ComputeData, Velocity};
+use crate::springs::{Spring, SpringTopology};
+
+// =========================================================================================
+//                                   IMPLICIT SOLVER
+// =========================================================================================
+// Backward Euler: the forces are evaluated at the end of the step instead of its start,
+//     M dv = h f(x + h (v + dv), v + dv)
+// Linearized around the current state, with K = df/dx and D = df/dv, this is
+//     (M - h D - h^2 K) dv = h f + h^2 K v
+// The matrix is symmetric positive definite as long as every spring Jacobian is negative
+// semi-definite, so the transverse part of the compressed springs is dropped. It is solved
+// with a Jacobi-preconditioned conjugate gradient that never stores the matrix: its product
+// with a vector is rebuilt from the springs of every vertex (implicit.wgsl on the GPU).
+// The step is stable for any stiffness, so stiff cloth runs with one or a few steps per frame.
+//
+// The result is turned back into a force, m dv / h, so that the integration pass of the
+// explicit solver (compute.wgsl) applies dv, moves the vertices and handles the collisions.
+// Pinned vertices are filtered out of the system, their dv is always 0.
+// =========================================================================================
+
+// Derivative of the force of a spring on its first vertex with respect to the position of
+// that vertex, with a = 1 - rest_length / length: -k (a I + (1 - a) n n^T).
+// A compressed spring (a < 0) would make the matrix indefinite, so a is clamped to 0.
+// Same as spring_jacobian in implicit.wgsl
+pub fn spring_jacobian(spring: &Spring, vertices: &[Vertex]) -> Matrix3<f32> {
+    let delta = Vector3::from(vertices[spring.index1 as usize].position) - Vector3::from(vertices[spring.index2 as usize].position);
+    let distance = delta.magnitude();
+    if distance < 1e-6 {
+        return Matrix3::from_value(0.0);
+    }
+    let n = delta / distance;
+    let a = (1.0 - spring.rest_length / distance).max(0.0);
+    let nn = Matrix3::from_cols(n * n.x, n * n.y, n * n.z);
+    (Matrix3::identity() * a + nn * (1.0 - a)) * -spring.stiffness
+}
+
+// The vectors of the conjugate gradient, allocated once
+pub struct ImplicitSolver {
+    pub iterations: u32, // maximum number of conjugate gradient iterations per step
+    dv: Vec<Vector3<f32>>,
+    r: Vec<Vector3<f32>>,
+    z: Vec<Vector3<f32>>,
+    p: Vec<Vector3<f32>>,
+    q: Vec<Vector3<f32>>,
+    diagonal: Vec<Vector3<f32>>,
+}
+
+impl ImplicitSolver {
+    pub fn new(nb_vertices: u32, iterations: u32) -> Self {
+        let zero = vec![Vector3::new(0.0, 0.0, 0.0); nb_vertices as usize];
+        Self {
+            iterations,
+            dv: zero.clone(),
+            r: zero.clone(),
+            z: zero.clone(),
+            p: zero.clone(),
+            q: zero.clone(),
+            diagonal: zero,
+        }
+    }
+
+    // Solve for the velocity change of the step from the forces of forces.wgsl, and replace
+    // them with m dv / h. Same passes as implicit.wgsl.
+    pub fn solve(
+        &mut self,
+        springs: &SpringTopology,
+        vertices: &[Vertex],
+        velocities: &[Velocity],
+        inverse_masses: &[f32],
+        data: &ComputeData,
+        forces: &mut [Vector3<f32>],
+    ) {
+        let h = data.delta_time;
+        let springs_of = |i: usize| {
+            let range = springs.ranges[i];
+            &springs.springs[range.offset as usize..(range.offset + range.count) as usize]
+        };
+
+        // init_main: b = h f + h^2 K v, dv = 0, r = b, z = r / diagonal, p = z
+        for i in 0..vertices.len() {
+            let mut b = Vector3::new(0.0, 0.0, 0.0);
+            let mut diagonal = Vector3::new(data.vertex_mass, data.vertex_mass, data.vertex_mass);
+            for spring in springs_of(i) {
+                let jacobian = spring_jacobian(spring, vertices);
+                let relative_velocity = Vector3::from(velocities[i].velocity) - Vector3::from(velocities[spring.index2 as usize].velocity);
+                b += h * h * (jacobian * relative_velocity);
+                let damping = h * spring.damping;
+                diagonal += Vector3::new(damping, damping, damping) - h * h * Vector3::new(jacobian.x.x, jacobian.y.y, jacobian.z.z);
+            }
+            b += h * forces[i];
+            if inverse_masses[i] == 0.0 {
+                b = Vector3::new(0.0, 0.0, 0.0);
+            }
+
+            let z = Vector3::new(b.x / diagonal.x, b.y / diagonal.y, b.z / diagonal.z);
+            self.dv[i] = Vector3::new(0.0, 0.0, 0.0);
+            self.r[i] = b;
+            self.z[i] = z;
+            self.p[i] = z;
+            self.diagonal[i] = diagonal;
+        }
+        let mut rz = dot(&self.r, &self.z);
+        let tolerance = data.cg_tolerance * data.cg_tolerance * rz;
+
+        for _ in 0..self.iterations {
+            if rz <= tolerance {
+                break;
+            }
+
+            // product_main: q = A p, the rows of the pinned vertices are 0
+            for (i, &inverse_mass) in inverse_masses.iter().enumerate() {
+                if inverse_mass == 0.0 {
+                    self.q[i] = Vector3::new(0.0, 0.0, 0.0);
+                    continue;
+                }
+                let mut result = data.vertex_mass * self.p[i];
+                for spring in springs_of(i) {
+                    let difference = self.p[i] - self.p[spring.index2 as usize];
+                    result += h * spring.damping * difference - h * h * (spring_jacobian(spring, vertices) * difference);
+                }
+                self.q[i] = result;
+            }
+            let pq = dot(&self.p, &self.q);
+            if pq <= 0.0 {
+                break;
+            }
+            let alpha = rz / pq;
+
+            // update_main: dv += alpha p, r -= alpha q, z = r / diagonal
+            for i in 0..vertices.len() {
+                self.dv[i] += alpha * self.p[i];
+                self.r[i] -= alpha * self.q[i];
+                let (r, diagonal) = (self.r[i], self.diagonal[i]);
+                self.z[i] = Vector3::new(r.x / diagonal.x, r.y / diagonal.y, r.z / diagonal.z);
+            }
+            let new_rz = dot(&self.r, &self.z);
+            let beta = if rz > 0.0 { new_rz / rz } else { 0.0 };
+            rz = new_rz;
+
+            // direction_main: p = z + beta p
+            for i in 0..vertices.len() {
+                self.p[i] = self.z[i] + beta * self.p[i];
+            }
+        }
+
+        // apply_main: the force that changes the velocity by dv during the step
+        for (force, dv) in forces.iter_mut().zip(&self.dv) {
+            *force = *dv * data.vertex_mass / h;
+        }
+    }
+}
+
+fn dot(a: &[Vector3<f32>], b: &[Vector3<f32>]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a.dot(*b)).sum()
+}
@@ -0,0 +1,298 @@
+// Compute shader of the implicit solver (see implicit.rs). One backward Euler step solves
+//     (M - h D - h^2 K) dv = h f + h^2 K v
+// for the velocity change dv with a Jacobi-preconditioned conjugate gradient. The matrix is
+// never stored: its product with a vector is rebuilt from the springs of every vertex.
+// forces.wgsl computes f before init_main, and apply_main turns dv back into a force so that
+// compute.wgsl integrates it and handles the collisions like for the explicit solver.
+//
+// 1. init_main, reduce_start_main
+// 2. every iteration: product_main, reduce_alpha_main, update_main, reduce_beta_main, direction_main
+// 3. apply_main
+
+struct Position {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    normal_x: f32,
+    normal_y: f32,
+    normal_z: f32,
+    tangent_x: f32,
+    tangent_y: f32,
+    tangent_z: f32,
+    tex_coords_x: f32,
+    tex_coords_y: f32,
+}
+
+struct Velocity {
+    velocity_x: f32,
+    velocity_y: f32,
+    velocity_z: f32,
+}
+
+struct Force {
+    force_x: f32,
+    force_y: f32,
+    force_z: f32,
+}
+
+struct ComputeData {
+    delta_time: f32,
+    nb_vertices: u32,
+    vertex_mass: f32,
+    nb_colliders: u32,
+    friction: f32,
+    restitution: f32,
+    damping: f32,
+    cg_tolerance: f32,        // The conjugate gradient stops when the residual is this fraction of the first one
+}
+
+struct Spring {
+    vertex_index_1: u32,
+    vertex_index_2: u32,
+    rest_length: f32,
+    stiffness: f32,
+    damping: f32,
+    spring_type: u32,
+}
+
+struct SpringRange {
+    offset: u32,
+    count: u32,
+}
+
+// The scalars of the conjugate gradient, then one partial sum per workgroup of 128 vertices
+struct SolverState {
+    rz: f32,       // r . z of the current residual
+    rz_start: f32, // r . z of the first residual
+    alpha: f32,    // step along the search direction, 0 once converged
+    beta: f32,     // weight of the previous search direction
+    partials: array<f32>,
+}
+
+@group(0) @binding(0) var<storage, read_write> verticiesPositions: array<Position>;
+@group(1) @binding(0) var<storage, read_write> verticiesVelocities: array<Velocity>;
+@group(1) @binding(1) var<storage, read_write> verticiesForces: array<Force>;
+@group(1) @binding(2) var<storage, read> inverseMasses: array<f32>; // 0 when pinned
+@group(2) @binding(0) var<uniform> data: ComputeData;
+@group(3) @binding(0) var<storage, read> springsR: array<Spring>;
+@group(3) @binding(1) var<storage, read> springRanges: array<SpringRange>;
+@group(3) @binding(2) var<storage, read_write> vectors: array<f32>; // 6 vectors of nb_vertices vec3, see below
+@group(3) @binding(3) var<storage, read_write> state: SolverState;
+
+// The vectors of the conjugate gradient, one after the other in `vectors`
+const DV: u32 = 0u;       // Solution: the velocity change
+const R: u32 = 1u;        // Residual
+const Z: u32 = 2u;        // Preconditioned residual
+const P: u32 = 3u;        // Search direction
+const Q: u32 = 4u;        // Matrix times the search direction
+const DIAGONAL: u32 = 5u; // Diagonal of the matrix, the Jacobi preconditioner
+
+var<workgroup> sums: array<f32, 128>;
+
+fn load(vector: u32, index: u32) -> vec3<f32> {
+    let base = (vector * data.nb_vertices + index) * 3u;
+    return vec3<f32>(vectors[base], vectors[base + 1u], vectors[base + 2u]);
+}
+
+fn store(vector: u32, index: u32, value: vec3<f32>) {
+    let base = (vector * data.nb_vertices + index) * 3u;
+    vectors[base] = value.x;
+    vectors[base + 1u] = value.y;
+    vectors[base + 2u] = value.z;
+}
+
+fn get_position(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+}
+
+// Derivative of the force of a spring on its first vertex with respect to the position of
+// that vertex: -k (a I + (1 - a) n n^T) with a = 1 - rest_length / length. A compressed spring
+// (a < 0) would make the matrix indefinite, its transverse part is dropped (same as spring_jacobian in implicit.rs).
+fn spring_jacobian(spring: Spring) -> mat3x3<f32> {
+    let delta = get_position(spring.vertex_index_1) - get_position(spring.vertex_index_2);
+    let distance = length(delta);
+    if (distance < 1e-6) {
+        return mat3x3<f32>(vec3<f32>(0.0), vec3<f32>(0.0), vec3<f32>(0.0));
+    }
+    let n = delta / distance;
+    let a = max(1.0 - spring.rest_length / distance, 0.0);
+    let nn = mat3x3<f32>(n * n.x, n * n.y, n * n.z);
+    let identity = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    return (identity * a + nn * (1.0 - a)) * -spring.stiffness;
+}
+
+// (A x)_i = m x_i + sum over the springs of (h c - h^2 K) (x_i - x_j), the damping force
+// -c (v_i - v_j) is linear so its derivative is exact. Pinned vertices do not move: their
+// rows and columns are filtered out.
+fn multiply(vector: u32, index: u32) -> vec3<f32> {
+    if (inverseMasses[index] == 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let h = data.delta_time;
+    var result = data.vertex_mass * load(vector, index);
+    let range = springRanges[index];
+    for (var i = range.offset; i < range.offset + range.count; i++) {
+        let spring = springsR[i];
+        // the vectors are always 0 on the pinned vertices
+        let difference = load(vector, index) - load(vector, spring.vertex_index_2);
+        result += h * spring.damping * difference - h * h * (spring_jacobian(spring) * difference);
+    }
+    return result;
+}
+
+// Write the sum of `value` over the workgroup in state.partials, must be called by every thread
+fn workgroup_sum(local: u32, group: u32, value: f32) {
+    sums[local] = value;
+    workgroupBarrier();
+    for (var stride = 64u; stride > 0u; stride /= 2u) {
+        if (local < stride) {
+            sums[local] += sums[local + stride];
+        }
+        workgroupBarrier();
+    }
+    if (local == 0u) {
+        state.partials[group] = sums[0];
+    }
+}
+
+// Sum of the partial sums of the workgroups, run by a single workgroup
+fn total(local: u32) -> f32 {
+    let nb_groups = (data.nb_vertices + 127u) / 128u;
+    var value = 0.0;
+    for (var i = local; i < nb_groups; i += 128u) {
+        value += state.partials[i];
+    }
+    sums[local] = value;
+    workgroupBarrier();
+    for (var stride = 64u; stride > 0u; stride /= 2u) {
+        if (local < stride) {
+            sums[local] += sums[local + stride];
+        }
+        workgroupBarrier();
+    }
+    return sums[0];
+}
+
+// b = h f + h^2 K v, dv = 0, so r = b, z = r / diagonal, p = z
+@compute @workgroup_size(128, 1, 1)
+fn init_main(@builtin(global_invocation_id) param: vec3<u32>, @builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    var rz = 0.0;
+    if (param.x < data.nb_vertices) {
+        let index = param.x;
+        let h = data.delta_time;
+        var b = vec3<f32>(0.0);
+        var diagonal = vec3<f32>(data.vertex_mass);
+        let velocity = vec3<f32>(verticiesVelocities[index].velocity_x, verticiesVelocities[index].velocity_y, verticiesVelocities[index].velocity_z);
+        let range = springRanges[index];
+        for (var i = range.offset; i < range.offset + range.count; i++) {
+            let spring = springsR[i];
+            let jacobian = spring_jacobian(spring);
+            let other = spring.vertex_index_2;
+            let other_velocity = vec3<f32>(verticiesVelocities[other].velocity_x, verticiesVelocities[other].velocity_y, verticiesVelocities[other].velocity_z);
+            b += h * h * (jacobian * (velocity - other_velocity));
+            diagonal += vec3<f32>(h * spring.damping) - h * h * vec3<f32>(jacobian[0].x, jacobian[1].y, jacobian[2].z);
+        }
+        b += h * vec3<f32>(verticiesForces[index].force_x, verticiesForces[index].force_y, verticiesForces[index].force_z);
+        if (inverseMasses[index] == 0.0) {
+            b = vec3<f32>(0.0);
+        }
+
+        let z = b / diagonal;
+        store(DV, index, vec3<f32>(0.0));
+        store(R, index, b);
+        store(Z, index, z);
+        store(P, index, z);
+        store(DIAGONAL, index, diagonal);
+        rz = dot(b, z);
+    }
+    workgroup_sum(local, group.x, rz);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn reduce_start_main(@builtin(local_invocation_index) local: u32) {
+    let rz = total(local);
+    if (local == 0u) {
+        state.rz = rz;
+        state.rz_start = rz;
+        state.alpha = 0.0;
+        state.beta = 0.0;
+    }
+}
+
+// q = A p
+@compute @workgroup_size(128, 1, 1)
+fn product_main(@builtin(global_invocation_id) param: vec3<u32>, @builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    var pq = 0.0;
+    if (param.x < data.nb_vertices) {
+        let q = multiply(P, param.x);
+        store(Q, param.x, q);
+        pq = dot(load(P, param.x), q);
+    }
+    workgroup_sum(local, group.x, pq);
+}
+
+// alpha = r.z / p.q, or 0 once the residual is small enough
+@compute @workgroup_size(128, 1, 1)
+fn reduce_alpha_main(@builtin(local_invocation_index) local: u32) {
+    let pq = total(local);
+    if (local == 0u) {
+        let tolerance = data.cg_tolerance * data.cg_tolerance * state.rz_start;
+        if (state.rz <= tolerance || pq <= 0.0) {
+            state.alpha = 0.0;
+        } else {
+            state.alpha = state.rz / pq;
+        }
+    }
+}
+
+// dv += alpha p, r -= alpha q, z = r / diagonal
+@compute @workgroup_size(128, 1, 1)
+fn update_main(@builtin(global_invocation_id) param: vec3<u32>, @builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    var rz = 0.0;
+    if (param.x < data.nb_vertices) {
+        let index = param.x;
+        let alpha = state.alpha;
+        store(DV, index, load(DV, index) + alpha * load(P, index));
+        let r = load(R, index) - alpha * load(Q, index);
+        let z = r / load(DIAGONAL, index);
+        store(R, index, r);
+        store(Z, index, z);
+        rz = dot(r, z);
+    }
+    workgroup_sum(local, group.x, rz);
+}
+
+// beta = new r.z / old r.z
+@compute @workgroup_size(128, 1, 1)
+fn reduce_beta_main(@builtin(local_invocation_index) local: u32) {
+    let rz = total(local);
+    if (local == 0u) {
+        if (state.rz > 0.0) {
+            state.beta = rz / state.rz;
+        } else {
+            state.beta = 0.0;
+        }
+        state.rz = rz;
+    }
+}
+
+// p = z + beta p
+@compute @workgroup_size(128, 1, 1)
+fn direction_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    store(P, param.x, load(Z, param.x) + state.beta * load(P, param.x));
+}
+
+// The force that changes the velocity by dv during the step: f = m dv / h
+@compute @workgroup_size(128, 1, 1)
+fn apply_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    let force = load(DV, param.x) * data.vertex_mass / data.delta_time;
+    verticiesForces[param.x].force_x = force.x;
+    verticiesForces[param.x].force_y = force.y;
+    verticiesForces[param.x].force_z = force.z;
+}
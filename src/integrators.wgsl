@@ -0,0 +1,187 @@
+// Compute shader of the explicit integrators other than the semi-implicit Euler of compute.wgsl,
+// selected with physics.integrator. The forces are computed by forces.wgsl before every
+// derivative evaluation and the collisions are handled afterwards by collide_main of compute.wgsl.
+//
+// Position Verlet, one pass per step:
+//     x(t + h) = x + (x - x(t - h)) + a h^2, v = (x(t + h) - x) / h
+// The last displacement x - x(t - h) is kept instead of x(t - h): with hundreds of sub-steps per
+// frame a h^2 is far below the precision of a f32 position, and would be lost in x - x(t - h).
+// RK4, the classic fourth order Runge-Kutta on the positions and velocities:
+//     rk4_begin_main, forces, rk4_stage1_main, forces, rk4_stage2_main, forces, rk4_stage3_main,
+//     forces, rk4_finish_main
+// Every stage moves the cloth to the state at which the next derivative is evaluated and adds
+// its derivative to a weighted sum, so the four stage derivatives are never stored at once.
+
+struct Position {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    normal_x: f32,
+    normal_y: f32,
+    normal_z: f32,
+    tangent_x: f32,
+    tangent_y: f32,
+    tangent_z: f32,
+    tex_coords_x: f32,
+    tex_coords_y: f32,
+}
+
+struct Velocity {
+    velocity_x: f32,
+    velocity_y: f32,
+    velocity_z: f32,
+}
+
+struct Force {
+    force_x: f32,
+    force_y: f32,
+    force_z: f32,
+}
+
+struct ComputeData {
+    delta_time: f32,
+    nb_vertices: u32,
+    vertex_mass: f32,
+    nb_colliders: u32,
+    friction: f32,
+    restitution: f32,
+    damping: f32,
+    cg_tolerance: f32,
+}
+
+@group(0) @binding(0) var<storage, read_write> verticiesPositions: array<Position>;
+@group(1) @binding(0) var<storage, read_write> verticiesVelocities: array<Velocity>;
+@group(1) @binding(1) var<storage, read_write> verticiesForces: array<Force>;
+@group(1) @binding(2) var<storage, read> inverseMasses: array<f32>; // 0 when pinned
+@group(2) @binding(0) var<uniform> data: ComputeData;
+@group(3) @binding(0) var<storage, read_write> state: array<f32>; // 4 vectors of nb_vertices vec3, see below
+
+// The vectors of the integrator state, one after the other in `state`
+const DISPLACEMENT: u32 = 0u; // Verlet: displacement of the last step, x - x(t - h)
+const PREDICTED: u32 = 1u;    // Verlet: positions at the end of the last step, before the collisions
+const X0: u32 = 0u;           // RK4: positions at the start of the step
+const V0: u32 = 1u;           // RK4: velocities at the start of the step
+const SUM_X: u32 = 2u;        // RK4: weighted sum of the derivatives of the positions
+const SUM_V: u32 = 3u;        // RK4: weighted sum of the derivatives of the velocities
+
+fn load(vector: u32, index: u32) -> vec3<f32> {
+    let base = (vector * data.nb_vertices + index) * 3u;
+    return vec3<f32>(state[base], state[base + 1u], state[base + 2u]);
+}
+
+fn store(vector: u32, index: u32, value: vec3<f32>) {
+    let base = (vector * data.nb_vertices + index) * 3u;
+    state[base] = value.x;
+    state[base + 1u] = value.y;
+    state[base + 2u] = value.z;
+}
+
+fn get_position(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+}
+
+fn set_position(index: u32, position: vec3<f32>) {
+    verticiesPositions[index].position_x = position.x;
+    verticiesPositions[index].position_y = position.y;
+    verticiesPositions[index].position_z = position.z;
+}
+
+fn get_velocity(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesVelocities[index].velocity_x, verticiesVelocities[index].velocity_y, verticiesVelocities[index].velocity_z);
+}
+
+fn set_velocity(index: u32, velocity: vec3<f32>) {
+    verticiesVelocities[index].velocity_x = velocity.x;
+    verticiesVelocities[index].velocity_y = velocity.y;
+    verticiesVelocities[index].velocity_z = velocity.z;
+}
+
+// Acceleration given by the forces of forces.wgsl, a = f / m
+fn acceleration(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesForces[index].force_x, verticiesForces[index].force_y, verticiesForces[index].force_z) * inverseMasses[index];
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn verlet_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    let index = param.x;
+    let h = data.delta_time;
+    let position = get_position(index);
+
+    // Pinned vertices have an infinite mass: they stay where they are
+    if (inverseMasses[index] == 0.0) {
+        store(DISPLACEMENT, index, vec3<f32>(0.0));
+        store(PREDICTED, index, position);
+        set_velocity(index, vec3<f32>(0.0));
+        return;
+    }
+
+    // A vertex moved by the collisions of the last step gets the displacement matching the
+    // velocity they left, so the bounce and the friction carry over
+    var displacement = load(DISPLACEMENT, index);
+    if (any(position != load(PREDICTED, index))) {
+        displacement = get_velocity(index) * h;
+    }
+
+    displacement += acceleration(index) * h * h;
+    let next = position + displacement;
+    store(DISPLACEMENT, index, displacement);
+    store(PREDICTED, index, next);
+    set_position(index, next);
+    set_velocity(index, displacement / h);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn rk4_begin_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    store(X0, param.x, get_position(param.x));
+    store(V0, param.x, get_velocity(param.x));
+    store(SUM_X, param.x, vec3<f32>(0.0));
+    store(SUM_V, param.x, vec3<f32>(0.0));
+}
+
+// Add the derivative at the current state with `weight`, then move to the state `step`
+// seconds after the start of the step along this derivative
+fn rk4_stage(index: u32, weight: f32, step: f32) {
+    if (index >= data.nb_vertices || inverseMasses[index] == 0.0) {
+        return;
+    }
+    let dx = get_velocity(index);
+    let dv = acceleration(index);
+    store(SUM_X, index, load(SUM_X, index) + weight * dx);
+    store(SUM_V, index, load(SUM_V, index) + weight * dv);
+    set_position(index, load(X0, index) + step * dx);
+    set_velocity(index, load(V0, index) + step * dv);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn rk4_stage1_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    rk4_stage(param.x, 1.0, 0.5 * data.delta_time);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn rk4_stage2_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    rk4_stage(param.x, 2.0, 0.5 * data.delta_time);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn rk4_stage3_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    rk4_stage(param.x, 2.0, data.delta_time);
+}
+
+// x = x0 + h / 6 (k1 + 2 k2 + 2 k3 + k4), same for v
+@compute @workgroup_size(128, 1, 1)
+fn rk4_finish_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    let index = param.x;
+    if (index >= data.nb_vertices || inverseMasses[index] == 0.0) {
+        return;
+    }
+    let sum_x = load(SUM_X, index) + get_velocity(index);
+    let sum_v = load(SUM_V, index) + acceleration(index);
+    set_position(index, load(X0, index) + data.delta_time / 6.0 * sum_x);
+    set_velocity(index, load(V0, index) + data.delta_time / 6.0 * sum_v);
+}
@@ -0,0 +1,66 @@
+// Interpolation compute shader, run once per frame before the rendering (see timestep.rs).
+// The simulation advances by fixed time steps, the frame is drawn between the cloth before
+// and after the last step, at the fraction of the next step already elapsed.
+
+struct Position {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    normal_x: f32,
+    normal_y: f32,
+    normal_z: f32,
+    tangent_x: f32,
+    tangent_y: f32,
+    tangent_z: f32,
+    tex_coords_x: f32,
+    tex_coords_y: f32,
+}
+
+struct InterpolationData {
+    alpha: f32,       // 0 draws the previous state, 1 the current one
+    nb_vertices: u32,
+}
+
+@group(0) @binding(0) var<uniform> data: InterpolationData;
+@group(0) @binding(1) var<storage, read> currentVertices: array<Position>;  // After the last step
+@group(0) @binding(2) var<storage, read> previousVertices: array<Position>; // Before the last step
+@group(0) @binding(3) var<storage, read_write> renderVertices: array<Position>; // Drawn by cloth.wgsl
+
+// Interpolated unit vector, the current one when the two cancel out
+fn blend_direction(previous: vec3<f32>, current: vec3<f32>) -> vec3<f32> {
+    let direction = mix(previous, current, data.alpha);
+    if (length(direction) < 1e-6) {
+        return current;
+    }
+    return normalize(direction);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    let current = currentVertices[param.x];
+    let previous = previousVertices[param.x];
+
+    let position = mix(
+        vec3<f32>(previous.position_x, previous.position_y, previous.position_z),
+        vec3<f32>(current.position_x, current.position_y, current.position_z),
+        data.alpha,
+    );
+    let normal = blend_direction(
+        vec3<f32>(previous.normal_x, previous.normal_y, previous.normal_z),
+        vec3<f32>(current.normal_x, current.normal_y, current.normal_z),
+    );
+    let tangent = blend_direction(
+        vec3<f32>(previous.tangent_x, previous.tangent_y, previous.tangent_z),
+        vec3<f32>(current.tangent_x, current.tangent_y, current.tangent_z),
+    );
+
+    renderVertices[param.x] = Position(
+        position.x, position.y, position.z,
+        normal.x, normal.y, normal.z,
+        tangent.x, tangent.y, tangent.z,
+        current.tex_coords_x, current.tex_coords_y,
+    );
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::mem::{offset_of, size_of};
+
+use naga::{ScalarKind, TypeInner};
+use wgpu_bootstrap::default::Vertex;
+
+use crate::colliders::Collider;
+use crate::cloth::{ComputeData, Force, Velocity};
+use crate::light::LightUniform;
+use crate::mesh::{MeshGrid, MeshTriangle};
+use crate::normals::TriangleRange;
+use crate::springs::{Spring, SpringRange};
+use crate::timestep::InterpolationData;
+use crate::xpbd::{ColorRange, XpbdConstraint};
+
+// =========================================================================================
+//                                    LAYOUT CHECK
+// =========================================================================================
+// The buffers are filled from #[repr(C)] Rust structs with bytemuck and read in the compute
+// shaders, and in the render shaders for the light uniform, through WGSL structs written by
+// hand. Nothing ties the two together, so a field added on one side only, or an f32 read as
+// an u32, silently corrupts the simulation.
+// At startup the shaders are parsed with naga and every WGSL struct is flattened into its
+// scalars, which must have the same offsets and types as the fields of the Rust struct.
+// =========================================================================================
+
+pub const FORCES_SHADER: &str = include_str!("forces.wgsl");
+// collisions.wgsl is shared by the integration of both solvers
+pub const COMPUTE_SHADER: &str = concat!(include_str!("compute.wgsl"), include_str!("collisions.wgsl"));
+pub const INTEGRATORS_SHADER: &str = include_str!("integrators.wgsl");
+pub const IMPLICIT_SHADER: &str = include_str!("implicit.wgsl");
+pub const XPBD_SHADER: &str = concat!(include_str!("xpbd.wgsl"), include_str!("collisions.wgsl"));
+pub const NORMALS_SHADER: &str = include_str!("normals.wgsl");
+pub const INTERPOLATE_SHADER: &str = include_str!("interpolate.wgsl");
+pub const STATS_SHADER: &str = include_str!("stats.wgsl");
+pub const CLOTH_SHADER: &str = include_str!("cloth.wgsl");
+pub const COLLIDER_SHADER: &str = include_str!("collider.wgsl");
+pub const GROUND_SHADER: &str = include_str!("ground.wgsl");
+pub const MESH_GRID_SHADER: &str = include_str!("mesh_grid.wgsl");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scalar {
+    F32,
+    U32,
+}
+
+// The flattened scalars of a Rust struct, with the name of the matching WGSL struct
+struct RustLayout {
+    rust_name: &'static str,
+    wgsl_name: &'static str,
+    size: usize,
+    scalars: Vec<(usize, Scalar)>,
+}
+
+// `field!(Type, field, Scalar, count)`: `count` scalars starting at the offset of the field
+macro_rules! field {
+    ($ty:ty, $field:ident, $scalar:expr, $count:expr) => {
+        (0..$count).map(|i| (offset_of!($ty, $field) + i * 4, $scalar))
+    };
+}
+
+fn rust_layouts() -> Vec<RustLayout> {
+    use Scalar::*;
+    vec![
+        RustLayout {
+            rust_name: "Vertex",
+            wgsl_name: "Position",
+            size: size_of::<Vertex>(),
+            scalars: field!(Vertex, position, F32, 3)
+                .chain(field!(Vertex, normal, F32, 3))
+                .chain(field!(Vertex, tangent, F32, 3))
+                .chain(field!(Vertex, tex_coords, F32, 2))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "Velocity",
+            wgsl_name: "Velocity",
+            size: size_of::<Velocity>(),
+            scalars: field!(Velocity, velocity, F32, 3).collect(),
+        },
+        RustLayout {
+            rust_name: "Force",
+            wgsl_name: "Force",
+            size: size_of::<Force>(),
+            scalars: field!(Force, force, F32, 3).collect(),
+        },
+        RustLayout {
+            rust_name: "ComputeData",
+            wgsl_name: "ComputeData",
+            size: size_of::<ComputeData>(),
+            scalars: field!(ComputeData, delta_time, F32, 1)
+                .chain(field!(ComputeData, nb_vertices, U32, 1))
+                .chain(field!(ComputeData, vertex_mass, F32, 1))
+                .chain(field!(ComputeData, nb_colliders, U32, 1))
+                .chain(field!(ComputeData, friction, F32, 1))
+                .chain(field!(ComputeData, restitution, F32, 1))
+                .chain(field!(ComputeData, damping, F32, 1))
+                .chain(field!(ComputeData, cg_tolerance, F32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "Collider",
+            wgsl_name: "Collider",
+            size: size_of::<Collider>(),
+            scalars: field!(Collider, shape, U32, 1)
+                .chain(field!(Collider, margin, F32, 1))
+                .chain(field!(Collider, friction, F32, 1))
+                .chain(field!(Collider, radius, F32, 1))
+                .chain(field!(Collider, half_height, F32, 1))
+                .chain(field!(Collider, center, F32, 3))
+                .chain(field!(Collider, half_extents, F32, 3))
+                .chain(field!(Collider, rotation, F32, 9))
+                .chain(field!(Collider, velocity, F32, 3))
+                .chain(field!(Collider, sdf_offset, U32, 1))
+                .chain(field!(Collider, sdf_resolution, U32, 3))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "MeshTriangle",
+            wgsl_name: "MeshTriangle",
+            size: size_of::<MeshTriangle>(),
+            scalars: field!(MeshTriangle, a, F32, 3)
+                .chain(field!(MeshTriangle, b, F32, 3))
+                .chain(field!(MeshTriangle, c, F32, 3))
+                .chain(field!(MeshTriangle, margin, F32, 1))
+                .chain(field!(MeshTriangle, friction, F32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "MeshGrid",
+            wgsl_name: "MeshGrid",
+            size: size_of::<MeshGrid>(),
+            scalars: field!(MeshGrid, origin_x, F32, 3)
+                .chain(field!(MeshGrid, inverse_cell_size, F32, 1))
+                .chain(field!(MeshGrid, cells_x, U32, 3))
+                .chain(field!(MeshGrid, nb_cells, U32, 1))
+                .chain(field!(MeshGrid, nb_triangles, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "Spring",
+            wgsl_name: "Spring",
+            size: size_of::<Spring>(),
+            scalars: field!(Spring, index1, U32, 1)
+                .chain(field!(Spring, index2, U32, 1))
+                .chain(field!(Spring, rest_length, F32, 1))
+                .chain(field!(Spring, stiffness, F32, 1))
+                .chain(field!(Spring, damping, F32, 1))
+                .chain(field!(Spring, spring_type, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "SpringRange",
+            wgsl_name: "SpringRange",
+            size: size_of::<SpringRange>(),
+            scalars: field!(SpringRange, offset, U32, 1)
+                .chain(field!(SpringRange, count, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "XpbdConstraint",
+            wgsl_name: "XpbdConstraint",
+            size: size_of::<XpbdConstraint>(),
+            scalars: field!(XpbdConstraint, kind, U32, 1)
+                .chain(field!(XpbdConstraint, vertices, U32, 4))
+                .chain(field!(XpbdConstraint, rest, F32, 1))
+                .chain(field!(XpbdConstraint, compliance, F32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "ColorRange",
+            wgsl_name: "ColorRange",
+            size: size_of::<ColorRange>(),
+            scalars: field!(ColorRange, offset, U32, 1)
+                .chain(field!(ColorRange, count, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "TriangleRange",
+            wgsl_name: "TriangleRange",
+            size: size_of::<TriangleRange>(),
+            scalars: field!(TriangleRange, offset, U32, 1)
+                .chain(field!(TriangleRange, count, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "InterpolationData",
+            wgsl_name: "InterpolationData",
+            size: size_of::<InterpolationData>(),
+            scalars: field!(InterpolationData, alpha, F32, 1)
+                .chain(field!(InterpolationData, nb_vertices, U32, 1))
+                .collect(),
+        },
+        RustLayout {
+            rust_name: "LightUniform",
+            wgsl_name: "Light",
+            size: size_of::<LightUniform>(),
+            scalars: field!(LightUniform, direction_x, F32, 3)
+                .chain(field!(LightUniform, color_r, F32, 3))
+                .chain(field!(LightUniform, ambient, F32, 1))
+                .chain(field!(LightUniform, specular, F32, 1))
+                .chain(field!(LightUniform, shininess, F32, 1))
+                .chain(field!(LightUniform, two_sided, U32, 1))
+                .chain(field!(LightUniform, camera_x, F32, 3))
+                .collect(),
+        },
+    ]
+}
+
+// A WGSL struct that does not match the Rust struct uploaded into it
+#[derive(Debug)]
+pub enum LayoutError {
+    Parse { shader: &'static str, reason: String },
+    Mismatch { shader: &'static str, rust_name: &'static str, wgsl_name: &'static str, reason: String },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Parse { shader, reason } => write!(f, "cannot parse {}: {}", shader, reason),
+            LayoutError::Mismatch { shader, rust_name, wgsl_name, reason } => write!(
+                f,
+                "the Rust struct {} does not match the struct {} of {}: {}",
+                rust_name, wgsl_name, shader, reason,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+// Check every Rust struct against the WGSL struct of the same role in every shader.
+// Structs a shader does not declare are skipped.
+pub fn check_shader_layouts() -> Result<(), LayoutError> {
+    let layouts = rust_layouts();
+    for (shader, source) in [
+        ("forces.wgsl", FORCES_SHADER),
+        ("compute.wgsl", COMPUTE_SHADER),
+        ("xpbd.wgsl", XPBD_SHADER),
+        ("implicit.wgsl", IMPLICIT_SHADER),
+        ("integrators.wgsl", INTEGRATORS_SHADER),
+        ("normals.wgsl", NORMALS_SHADER),
+        ("interpolate.wgsl", INTERPOLATE_SHADER),
+        ("stats.wgsl", STATS_SHADER),
+        ("cloth.wgsl", CLOTH_SHADER),
+        ("collider.wgsl", COLLIDER_SHADER),
+        ("ground.wgsl", GROUND_SHADER),
+        ("mesh_grid.wgsl", MESH_GRID_SHADER),
+    ] {
+        let module = naga::front::wgsl::parse_str(source).map_err(|err| LayoutError::Parse {
+            shader,
+            reason: err.emit_to_string(source),
+        })?;
+        for layout in &layouts {
+            check_struct(&module, layout).map_err(|reason| LayoutError::Mismatch {
+                shader,
+                rust_name: layout.rust_name,
+                wgsl_name: layout.wgsl_name,
+                reason,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_struct(module: &naga::Module, layout: &RustLayout) -> Result<(), String> {
+    let Some((handle, ty)) = module.types.iter().find(|(_, ty)| ty.name.as_deref() == Some(layout.wgsl_name)) else {
+        return Ok(());
+    };
+    let TypeInner::Struct { span, .. } = ty.inner else {
+        return Err(String::from("the WGSL type is not a struct"));
+    };
+
+    if span as usize != layout.size {
+        return Err(format!("size is {} bytes in Rust and {} bytes in WGSL", layout.size, span));
+    }
+
+    let mut scalars = Vec::new();
+    flatten(module, handle, 0, &mut scalars)?;
+    if scalars.len() != layout.scalars.len() {
+        return Err(format!("{} scalars in Rust and {} in WGSL", layout.scalars.len(), scalars.len()));
+    }
+    for (i, (rust, wgsl)) in layout.scalars.iter().zip(&scalars).enumerate() {
+        if rust != wgsl {
+            return Err(format!(
+                "scalar {} is {:?} at offset {} in Rust and {:?} at offset {} in WGSL",
+                i, rust.1, rust.0, wgsl.1, wgsl.0,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Append the scalars of a WGSL type, in memory order, with their byte offset
+fn flatten(module: &naga::Module, handle: naga::Handle<naga::Type>, offset: usize, scalars: &mut Vec<(usize, Scalar)>) -> Result<(), String> {
+    let scalar = |scalar: naga::Scalar| match (scalar.kind, scalar.width) {
+        (ScalarKind::Float, 4) => Ok(Scalar::F32),
+        (ScalarKind::Uint, 4) => Ok(Scalar::U32),
+        (kind, width) => Err(format!("unsupported WGSL scalar {:?} of {} bytes", kind, width)),
+    };
+
+    match module.types[handle].inner {
+        TypeInner::Scalar(s) => scalars.push((offset, scalar(s)?)),
+        TypeInner::Vector { size, scalar: s } => {
+            for i in 0..size as usize {
+                scalars.push((offset + i * s.width as usize, scalar(s)?));
+            }
+        }
+        TypeInner::Array { base, size: naga::ArraySize::Constant(count), stride } => {
+            for i in 0..count.get() as usize {
+                flatten(module, base, offset + i * stride as usize, scalars)?;
+            }
+        }
+        TypeInner::Struct { ref members, .. } => {
+            for member in members {
+                flatten(module, member.ty, offset + member.offset as usize, scalars)?;
+            }
+        }
+        ref other => return Err(format!("unsupported WGSL type {:?}", other)),
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+// Mass-spring cloth simulation running in wgpu compute shaders.
+//
+// `ClothSimulation` owns the cloth buffers and the compute pipelines and only needs a wgpu
+// device and queue, it is independent from the window and the `Application` of the demo.
+// `CpuSolver` is the same model in pure Rust, used as a reference and as a fallback.
+// `FixedTimestep` turns the frame durations into fixed simulation steps.
+// `render::SceneRenderer` draws the simulation in a wgpu-bootstrap window.
+// `layout::check_shader_layouts` checks that the Rust buffer structs match the WGSL ones.
+
+pub mod camera;
+pub mod cloth;
+pub mod colliders;
+pub mod cpu;
+pub mod headless;
+pub mod implicit;
+pub mod layout;
+pub mod light;
+pub mod mesh;
+pub mod motion;
+pub mod normals;
+pub mod render;
+pub mod scene;
+pub mod sdf;
+pub mod simulation;
+pub mod springs;
+pub mod substeps;
+pub mod timestep;
+pub mod validate;
+pub mod xpbd;
+
+pub use cpu::CpuSolver;
+pub use scene::{Scene, SceneError};
+pub use simulation::ClothSimulation;
+pub use timestep::FixedTimestep;
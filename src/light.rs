@@ -0,0 +1,56 @@
+use wgpu_bootstrap::cgmath::{InnerSpace, Vector3};
+
+use crate::scene::LightConfig;
+
+// =========================================================================================
+//                                      LIGHTING
+// =========================================================================================
+// Uniform read by the fragment shader of cloth.wgsl: one directional light with an ambient
+// term and an optional Blinn-Phong highlight. The camera position is needed for the
+// highlight, it is rewritten whenever the camera moves.
+// =========================================================================================
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    pub direction_x: f32, // normalized direction in which the light travels
+    pub direction_y: f32,
+    pub direction_z: f32,
+    pub color_r: f32,
+    pub color_g: f32,
+    pub color_b: f32,
+    pub ambient: f32,
+    pub specular: f32, // strength of the highlight, 0 disables it
+    pub shininess: f32,
+    pub two_sided: u32, // 1 to light the back faces with the flipped normal
+    pub camera_x: f32,
+    pub camera_y: f32,
+    pub camera_z: f32,
+}
+
+impl LightUniform {
+    pub fn new(light: &LightConfig, camera_position: [f32; 3]) -> Self {
+        let direction = Vector3::from(light.direction).normalize();
+        Self {
+            direction_x: direction.x,
+            direction_y: direction.y,
+            direction_z: direction.z,
+            color_r: light.color[0],
+            color_g: light.color[1],
+            color_b: light.color[2],
+            ambient: light.ambient,
+            specular: light.specular,
+            shininess: light.shininess,
+            two_sided: light.two_sided as u32,
+            camera_x: camera_position[0],
+            camera_y: camera_position[1],
+            camera_z: camera_position[2],
+        }
+    }
+
+    pub fn set_camera_position(&mut self, camera_position: [f32; 3]) {
+        self.camera_x = camera_position[0];
+        self.camera_y = camera_position[1];
+        self.camera_z = camera_position[2];
+    }
+}
@@ -1,597 +1,325 @@
 use wgpu_bootstrap::{
     window::Window,
-    frame::Frame,
     application::Application,
     context::Context,
-    geometry::icosphere,
-    camera::Camera,
     wgpu,
-    cgmath,
-    default::Vertex,
-    computation::Computation,
-    texture::create_texture_bind_group,
+    winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct ComputeData {
-    delta_time: f32,
-    nb_vertices: f32,
-    sphere_radius: f32,
-    sphere_center_x: f32,
-    sphere_center_y: f32,
-    sphere_center_z: f32,
-    vertex_mass: f32,
-    structural_stiffness: f32,
-    shear_stiffness: f32,
-    bend_stiffness: f32,
-    structural_damping: f32,
-    shear_damping: f32,
-    bend_damping: f32,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Velocity {
-    pub velocity: [f32; 3]
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Spring {
-    pub index1: f32,
-    pub index2: f32,
-    pub rest_length: f32,
-}
-// =========================================================================================
-//                                        PARAMETERS
-// =========================================================================================
-// CLOTH
-const CLOTH_SIZE: f32 = 50.0;
-const N_CLOTH_VERTICES_PER_ROW: u32 = 25; // the cloth is a square, the minimum is 2
-const CLOTH_CENTER_X: f32 = 0.0;
-const CLOTH_CENTER_Y: f32 = 10.0;
-const CLOTH_CENTER_Z: f32 = 0.0;
-// SPHERE
-const SPHERE_RADIUS: f32 = 10.0;
-const SPHERE_CENTER_X: f32 = 0.0;
-const SPHERE_CENTER_Y: f32 = 0.0;
-const SPHERE_CENTER_Z: f32 = 0.0;
-// PHYSICS
-const VERTEX_MASS: f32 = 0.16;
-const STRUCTURAL_STIFFNESS: f32 = 150.0;
-const SHEAR_STIFFNESS: f32 = 5.0;
-const BEND_STIFFNESS: f32 = 15.0;
-const STRUCTURAL_DAMPING: f32 = 1.5;
-const SHEAR_DAMPING: f32 = 0.05;
-const BEND_DAMPING: f32 = 0.15;
-
-const N_ITERATIONS: u32 = 500; 
-// =========================================================================================
+use cloth_simulation::{
+    camera::{OrbitCamera, OrbitControls},
+    cloth::pinned_vertices,
+    headless,
+    layout,
+    mesh::ObjMesh,
+    render::SceneRenderer,
+    sdf::{SdfGrid, MAX_SDF_RESOLUTION},
+    validate,
+    ClothSimulation,
+    FixedTimestep,
+    Scene,
+};
 
+// The example application: the simulation of a scene drawn in a window, with the camera
+// controls, W to show the wireframe of the colliders and P to release the pins
 struct MyApp {
-    camera_bind_group: wgpu::BindGroup,
-    texture_bind_group: wgpu::BindGroup,
-    // sphere
-    sphere_pipeline: wgpu::RenderPipeline,
-    sphere_vertex_buffer: wgpu::Buffer, // All the vertices of the sphere (all stored to be accessed by the GPU)
-    sphere_index_buffer: wgpu::Buffer, // All the indices of the sphere, how to assemble the vertices
-    sphere_indices: Vec<u16>,
-    // cloth
-    cloth_pipeline: wgpu::RenderPipeline,
-    cloth_vertex_buffer: wgpu::Buffer,
-    cloth_index_buffer: wgpu::Buffer,
-    cloth_indices: Vec<u16>,
-    // compute
-    compute_pipeline: wgpu::ComputePipeline,
-    forces_compute_pipeline: wgpu::ComputePipeline,
-    compute_vertices_bind_group: wgpu::BindGroup,
-    compute_data_bind_group: wgpu::BindGroup,
-    compute_velocities_bind_group: wgpu::BindGroup,
-    compute_data_buffer: wgpu::Buffer,
-    compute_data: ComputeData,
-    // spring
-    springs_bind_group: wgpu::BindGroup,
+    camera: OrbitCamera,
+    controls: OrbitControls,
+    renderer: SceneRenderer,
+    simulation: ClothSimulation,
+    timestep: FixedTimestep, // the frame durations turned into fixed simulation steps
+    report_time: f32, // seconds since the sub-steps and the dropped time were last printed
+    reported_dropped_time: f64, // dropped time of the fixed time step at the last print
+    // pins of the scene, released and pinned again with the P key
+    pinned_vertices: Vec<u32>,
+    pins_enabled: bool,
 }
 
 impl MyApp {
-    fn new(context: &Context) -> Self {
-        let texture = context.create_texture(
-            "English",
-            include_bytes!("texture.jpg"),
-        );
-
-        let texture_bind_group = create_texture_bind_group(context, &texture);
-
-        // =====================================================================
-        //                              Camera
-        // =====================================================================
-        let camera = Camera {
-            eye: (20.0, 50.0, 50.0).into(),
-            target: (0.0, 0.0, 0.0).into(),
-            up: cgmath::Vector3::unit_y(),
-            aspect: context.get_aspect_ratio(),
-            fovy: 75.0,
-            znear: 0.1,
-            zfar: 1000.0,
-        };
-
-        let (_camera_buffer, camera_bind_group) = camera.create_camera_bind_group(context);
-
-        // =====================================================================
-        //                              Sphere
-        // =====================================================================
-        let sphere_pipeline = context.create_render_pipeline(
-            "Render Pipeline Sphere",
-            include_str!("sphere.wgsl"),
-            &[Vertex::desc()],
-            &[&context.camera_bind_group_layout],
-            wgpu::PrimitiveTopology::LineList
-        );
-
-        let (mut sphere_vertices, sphere_indices) = icosphere(4);
-
-        // change the radius of the sphere :
-        for vertex in sphere_vertices.iter_mut() {
-            let mut posn = cgmath::Vector3::from(vertex.position);
-            posn *= SPHERE_RADIUS as f32;
-            vertex.position = posn.into()
-        }
-        // change the center of the sphere :
-        for vertex in sphere_vertices.iter_mut() {
-            vertex.position[0] += SPHERE_CENTER_X;
-            vertex.position[1] += SPHERE_CENTER_Y;
-            vertex.position[2] += SPHERE_CENTER_Z;
+    fn new(context: &Context, scene: &Scene) -> Self {
+        // orbit camera starting at the camera of the scene
+        let camera = OrbitCamera::new(&scene.camera);
+        // create the cloth, its buffers are updated by the compute shaders of the simulation
+        let simulation = ClothSimulation::new(&context.device, scene);
+        let renderer = SceneRenderer::new(context, scene, &simulation, &camera);
+
+        Self {
+            camera,
+            controls: OrbitControls::default(),
+            renderer,
+            simulation,
+            timestep: FixedTimestep::from_scene(scene),
+            report_time: 0.0,
+            reported_dropped_time: 0.0,
+            pinned_vertices: pinned_vertices(scene),
+            pins_enabled: true,
         }
+    }
+}
 
-        // create a buffer for the sphere
-        let sphere_vertex_buffer = context.create_buffer(
-            &sphere_vertices,
-            wgpu::BufferUsages::VERTEX
-        );
-        let sphere_index_buffer = context.create_buffer(
-            &sphere_indices,
-            wgpu::BufferUsages::INDEX
-        );
-
-
-        // =====================================================================
-        //                              Cloth
-        // =====================================================================
-        let cloth_pipeline = context.create_render_pipeline(
-            "Render Pipeline Cloth",
-            include_str!("cloth.wgsl"),
-            &[Vertex::desc()],
-            &[
-                &context.texture_bind_group_layout,
-                &context.camera_bind_group_layout,
-                ],
-            wgpu::PrimitiveTopology::TriangleList
-        );
+impl Application for MyApp {
+    // Function to render the scene
+    fn render(&self, context: &Context) -> Result<(), wgpu::SurfaceError> {
+        self.renderer.render(context, &self.simulation)
+    }
 
-        
-        // create the cloth
-        let mut cloth_vertices = Vec::new();
-        let mut cloth_indices: Vec<u16> = Vec::new();
-        
-        // create the vertices
-        for i in 0..N_CLOTH_VERTICES_PER_ROW {
-            for j in 0..N_CLOTH_VERTICES_PER_ROW {
-                cloth_vertices.push(Vertex {
-                    position: [
-                        CLOTH_CENTER_X + i as f32 * (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) - (CLOTH_SIZE / 2.0),
-                        CLOTH_CENTER_Y,
-                        CLOTH_CENTER_Z + j as f32 * (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) - (CLOTH_SIZE / 2.0),
-                    ],
-                    normal: [0.0, 0.0, 0.0],
-                    tangent: [0.0, 0.0, 0.0],
-                    tex_coords: [
-                        i as f32 * (1.0 / (N_CLOTH_VERTICES_PER_ROW - 1) as f32),
-                        j as f32 * (1.0 / (N_CLOTH_VERTICES_PER_ROW - 1) as f32),
-                    ],
-                });
+    // Function to handle the keyboard and the mouse
+    fn input(&mut self, context: &Context, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                ..
+            },
+            ..
+        } = event
+        {
+            match key {
+                // Release the pinned vertices, or pin them again where they are now
+                VirtualKeyCode::P => {
+                    self.pins_enabled = !self.pins_enabled;
+                    self.simulation.set_pinned(&context.queue, &self.pinned_vertices, self.pins_enabled);
+                    return true;
+                }
+                // Show or hide the wireframe of the colliders
+                VirtualKeyCode::W => {
+                    self.renderer.show_wireframe = !self.renderer.show_wireframe;
+                    return true;
+                }
+                _ => {}
             }
         }
+        self.controls.input(&mut self.camera, event)
+    }
 
-        // create the indices
-        for i in 0..N_CLOTH_VERTICES_PER_ROW - 1 {
-            for j in 0..N_CLOTH_VERTICES_PER_ROW - 1 {
-                // first triangle
-                cloth_indices.push((i * N_CLOTH_VERTICES_PER_ROW + j) as u16);
-                cloth_indices.push((i * N_CLOTH_VERTICES_PER_ROW + j + 1) as u16);
-                cloth_indices.push(((i + 1) * N_CLOTH_VERTICES_PER_ROW + j) as u16);
-                // second triangle
-                cloth_indices.push((i * N_CLOTH_VERTICES_PER_ROW + j + 1) as u16);
-                cloth_indices.push(((i + 1) * N_CLOTH_VERTICES_PER_ROW + j + 1) as u16);
-                cloth_indices.push(((i + 1) * N_CLOTH_VERTICES_PER_ROW + j) as u16);
-            }
+    // Function to update simulation data
+    fn update(&mut self, context: &Context, delta_time: f32) {
+        self.controls.update(&mut self.camera, delta_time);
+        if let Some(offset) = self.controls.take_sphere_drag() {
+            self.simulation.push_sphere(offset.into());
         }
 
-        // set the default speed of the cloth
-        let mut cloth_velocities: Vec<Velocity> = Vec::new();
-        for _i in cloth_vertices.iter_mut() {
-            cloth_velocities.push(Velocity {
-                velocity: [0.0, 0.0, 0.0],
-            });
+        // Run the fixed time steps that fit in the frame, each one divided between the
+        // iterations for stability. The colliders are kept where they were before the last step.
+        let steps = self.timestep.advance(delta_time);
+        for _ in 0..steps {
+            self.renderer.save_collider_models(&self.simulation);
+            self.simulation.step(&context.device, &context.queue, self.timestep.time_step);
         }
 
-        // create a buffer for the cloth
-        let cloth_vertex_buffer = context.create_buffer(
-            &cloth_vertices,
-            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
-        );
-        let cloth_index_buffer = context.create_buffer(
-            &cloth_indices,
-            wgpu::BufferUsages::INDEX
-        );
-        let cloth_velocities_buffer = context.create_buffer(
-            &cloth_velocities,
-            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX
-        );
+        // Print the sub-steps chosen by the adaptive sub-steps, and the simulation time dropped
+        // by physics.max_steps_per_frame when the frames are too slow, once per second
+        self.report_time += delta_time;
+        if self.report_time >= 1.0 {
+            if self.simulation.substep_controller().is_some() {
+                println!("adaptive: {} sub-steps per step", self.simulation.iterations());
+                if let Some(err) = self.simulation.last_stats_error() {
+                    eprintln!("adaptive: cannot read back the motion stats, the count is kept: {}", err);
+                }
+            }
+            let dropped_time = self.timestep.dropped_time();
+            if dropped_time > self.reported_dropped_time {
+                println!(
+                    "timestep: {:.3}s dropped in the last second, {:.3}s since the start, the frames need more than {} steps",
+                    dropped_time - self.reported_dropped_time,
+                    dropped_time,
+                    self.timestep.max_steps
+                );
+                self.reported_dropped_time = dropped_time;
+            }
+            self.report_time = 0.0;
+        }
 
-        // create the compute pipeline
-        let compute_pipeline = context.create_compute_pipeline(
-            "Compute Pipeline",
-            include_str!("compute.wgsl"),
-        );
-        // create the force compute pipeline
-        let forces_compute_pipeline = context.create_compute_pipeline(
-            "Forces Compute Pipeline",
-            include_str!("forces.wgsl")
-        );
+        // Draw the cloth and the colliders between the last two steps, at the fraction of the
+        // next step already elapsed, so the motion stays smooth at any frame rate
+        let alpha = self.timestep.alpha();
+        self.simulation.interpolate(&context.device, &context.queue, alpha);
+        self.renderer.update(context, &self.camera, &self.simulation, alpha);
+    }
+}
 
-        let compute_vertices_bind_group = context.create_bind_group(
-            "compute vertices bind group",
-            &compute_pipeline.get_bind_group_layout(0),
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: cloth_vertex_buffer.as_entire_binding(),
-                },
-            ],
-        );
-        let compute_velocities_bind_group = context.create_bind_group(
-            "compute velocities bind group",
-            &compute_pipeline.get_bind_group_layout(1),
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: cloth_velocities_buffer.as_entire_binding(),
-                },
-            ],
-        );
+// Command line: cloth_simulation [SCENE] [--headless FRAMES] [--dt SECONDS] [--output FILE] [--cpu]
+//                                       [--validate ITERATIONS] [--tolerance DISTANCE]
+struct Options {
+    scene: Option<String>,
+    headless_frames: Option<u32>,
+    validate_iterations: Option<u32>,
+    tolerance: f32,
+    backend: headless::Backend,
+    delta_time: f32,
+    output: Option<String>,
+    bake_sdf: Option<String>, // OBJ mesh baked into a signed distance field written to `output`
+    sdf_resolution: u32,      // samples along the largest side of the baked mesh
+}
 
-        // =====================================================================
-        //                              Compute Data
-        // =====================================================================
-        let compute_data = ComputeData {
-            delta_time: 0.01,
-            nb_vertices: (N_CLOTH_VERTICES_PER_ROW*N_CLOTH_VERTICES_PER_ROW) as f32,
-            sphere_radius: SPHERE_RADIUS,
-            sphere_center_x: SPHERE_CENTER_X,
-            sphere_center_y: SPHERE_CENTER_Y,
-            sphere_center_z: SPHERE_CENTER_Z,
-            vertex_mass: VERTEX_MASS,
-            structural_stiffness: STRUCTURAL_STIFFNESS,
-            shear_stiffness: SHEAR_STIFFNESS,
-            bend_stiffness: BEND_STIFFNESS,
-            structural_damping: STRUCTURAL_DAMPING,
-            shear_damping: SHEAR_DAMPING,
-            bend_damping: BEND_DAMPING,
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            scene: None,
+            headless_frames: None,
+            validate_iterations: None,
+            tolerance: 1e-3,
+            backend: headless::Backend::Gpu,
+            delta_time: 1.0 / 60.0,
+            output: None,
+            bake_sdf: None,
+            sdf_resolution: 32,
         };
-
-        let compute_data_buffer = context.create_buffer(
-            &[compute_data],
-            wgpu::BufferUsages::UNIFORM,
-        );
-
-        let compute_data_bind_group = context.create_bind_group(
-            "compute data bind group",
-            &compute_pipeline.get_bind_group_layout(2),
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: compute_data_buffer.as_entire_binding(),
-                },
-            ],
-        );
-        
-        // =====================================================================
-        //                              Springs
-        // =====================================================================
-        let mut springs: Vec<Spring> = Vec::new();
-        for i in 0..N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW {
-             // Calculate the row and column of the current vertex
-            let col: i32 = (i % N_CLOTH_VERTICES_PER_ROW) as i32;
-            let row: i32 = (i / N_CLOTH_VERTICES_PER_ROW) as i32;
-
-            // Structural springs: connect current vertex to its horizontal and vertical neighbors
-            for j in [-1, 1] as [i32; 2] {
-                // Horizontal neighbors (col +/- 1)
-                let mut index2 = row * N_CLOTH_VERTICES_PER_ROW as i32 + col + j;
-                // Check if the neighbor is out of bounds (left or right edge)
-                if col + j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || col + j < 0 {
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-                // Add a spring connecting the current vertex to its horizontal neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Neighbor vertex index
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32), // Resting length of the spring
-                });
-
-                // Vertical neighbors (row +/- 1)
-                index2 = (row + j) * N_CLOTH_VERTICES_PER_ROW as i32 + col;
-                // Check if the neighbor is out of bounds (top or bottom edge)
-                if row + j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || row + j < 0 {
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-                // Add a spring connecting the current vertex to its vertical neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Neighbor vertex index
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32), // Resting length of the spring
-                });
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless_frames = Some(parse_value(&arg, args.next())?),
+                "--dt" => options.delta_time = parse_value(&arg, args.next())?,
+                "--output" => options.output = Some(parse_value(&arg, args.next())?),
+                "--cpu" => options.backend = headless::Backend::Cpu,
+                "--validate" => options.validate_iterations = Some(parse_value(&arg, args.next())?),
+                "--tolerance" => options.tolerance = parse_value(&arg, args.next())?,
+                "--bake-sdf" => options.bake_sdf = Some(parse_value(&arg, args.next())?),
+                "--resolution" => options.sdf_resolution = parse_value(&arg, args.next())?,
+                _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+                _ if options.scene.is_none() => options.scene = Some(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
             }
-            // Shear springs: connect current vertex to its diagonal neighbors
-            for j in [-1, 1] as [i32; 2] {
-                // Diagonal neighbors: bottom-right and top-left (row +/- j, col +/- j)
-                let mut index2 = (row + j) * N_CLOTH_VERTICES_PER_ROW as i32 + col + j;
-                // Check if the neighbor is out of bounds
-                if col + j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || col + j < 0 || row + j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || row + j < 0 {
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-                // Add a shear spring connecting the current vertex to the diagonal neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Diagonal neighbor index
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) * 1.41421356237, // Diagonal resting length
-                });
-
-                // Diagonal neighbors: bottom-left and top-right (row +/- j, col -/+ j)
-                index2 = (row - j) * N_CLOTH_VERTICES_PER_ROW as i32 + col + j;
-                // Check if the neighbor is out of bounds
-                if col + j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || col + j < 0 || row - j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || row - j < 0 {
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-                // Add a shear spring connecting the current vertex to the diagonal neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Diagonal neighbor index
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) * 1.41421356237, // Diagonal resting length
-                });
+        }
+        if !(options.delta_time.is_finite() && options.delta_time > 0.0) {
+            return Err(format!("--dt must be a positive number, got {}", options.delta_time));
+        }
+        if options.bake_sdf.is_some() {
+            if options.output.is_none() {
+                return Err(String::from("--bake-sdf needs --output"));
             }
-            // Bend springs: Adding structural springs to connect vertices that are two steps apart.
-            for j in [-1,1] as [i32; 2] {
-                // Horizontal bend springs: col ± 2j
-                let mut index2 = row * N_CLOTH_VERTICES_PER_ROW as i32 + col + 2 * j;
-                
-                // Check if the neighbor (col ± 2j) is within the grid boundaries
-                if col + 2 * j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || col + 2 * j < 0 {
-                    // If out of bounds, assign an invalid index to avoid creating a spring
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-                
-                // Add a bend spring between the current vertex and its horizontal neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Horizontal neighbor index (two steps away)
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) * 2.0, // Rest length is double the horizontal step
-                });
-
-                // Vertical bend springs: row ± 2j
-                index2 = (row + 2 * j) * N_CLOTH_VERTICES_PER_ROW as i32 + col;
-                
-                // Check if the neighbor (row ± 2j) is within the grid boundaries
-                if row + 2 * j > N_CLOTH_VERTICES_PER_ROW as i32 - 1 || row + 2 * j < 0 {
-                    // If out of bounds, assign an invalid index to avoid creating a spring
-                    index2 = (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW + 1) as i32;
-                }
-
-                // Add a bend spring between the current vertex and its vertical neighbor
-                springs.push(Spring {
-                    index1: i as f32, // Current vertex index
-                    index2: index2 as f32, // Vertical neighbor index (two steps away)
-                    rest_length: (CLOTH_SIZE / (N_CLOTH_VERTICES_PER_ROW - 1) as f32) * 2.0, // Rest length is double the vertical step
-                });
+            if options.headless_frames.is_some() || options.validate_iterations.is_some() {
+                return Err(String::from("--bake-sdf cannot be used with --headless or --validate"));
             }
+            if !(2..=MAX_SDF_RESOLUTION).contains(&options.sdf_resolution) {
+                return Err(format!("--resolution must be between 2 and {}, got {}", MAX_SDF_RESOLUTION, options.sdf_resolution));
+            }
+        } else if options.output.is_some() && options.headless_frames.is_none() {
+            return Err(String::from("--output can only be used with --headless or --bake-sdf"));
         }
-
-        // create a buffer for the springs
-        let springs_buffer = context.create_buffer(
-            springs.as_slice(),
-            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
-        );
-        // create a bind group for the springs
-        let springs_bind_group = context.create_bind_group(
-            "Sping Bind Group",
-            &compute_pipeline.get_bind_group_layout(3),
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: springs_buffer.as_entire_binding(),
-                },
-            ]
-        );
-
-        return Self {
-            camera_bind_group,
-            texture_bind_group,
-            // sphere
-            sphere_pipeline,
-            sphere_vertex_buffer,
-            sphere_index_buffer,
-            sphere_indices,
-            // cloth
-            cloth_pipeline,
-            cloth_vertex_buffer,
-            cloth_index_buffer,
-            cloth_indices,
-            // compute
-            compute_pipeline,
-            forces_compute_pipeline,
-            compute_vertices_bind_group,
-            compute_velocities_bind_group,
-            compute_data_bind_group,
-            compute_data_buffer,
-            compute_data,
-            // springs
-            springs_bind_group,
-        };
+        if options.backend == headless::Backend::Cpu && options.headless_frames.is_none() {
+            return Err(String::from("--cpu can only be used with --headless"));
+        }
+        if options.headless_frames.is_some() && options.validate_iterations.is_some() {
+            return Err(String::from("--headless and --validate cannot be used together"));
+        }
+        if options.tolerance.is_nan() || options.tolerance < 0.0 {
+            return Err(format!("--tolerance must be zero or a positive number, got {}", options.tolerance));
+        }
+        Ok(options)
     }
-    
 }
 
-impl Application for MyApp {
-    // Function to render the scene
-    fn render(&self, context: &Context) -> Result<(), wgpu::SurfaceError> {
-        // Create a new frame to render into
-        let mut frame = Frame::new(context)?;
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for '{}'", option))?;
+    value.parse().map_err(|_| format!("invalid value '{}' for '{}'", value, option))
+}
 
-        {
-            // Begin a new render pass with a background color (light gray)
-            let mut render_pass = frame.begin_render_pass(wgpu::Color {
-                r: 0.85, // Red component of the background color
-                g: 0.85, // Green component of the background color
-                b: 0.85, // Blue component of the background color
-                a: 1.0,  // Alpha (opacity) set to fully opaque
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: cloth_simulation [SCENE] [--headless FRAMES] [--dt SECONDS] [--output FILE] [--cpu]");
+            eprintln!("                        [--validate ITERATIONS] [--tolerance DISTANCE]");
+            eprintln!("       cloth_simulation --bake-sdf MESH.obj --output FILE.sdf [--resolution SAMPLES]");
+            std::process::exit(2);
+        }
+    };
+
+    // Bake mode: write the signed distance field of a closed mesh for an sdf collider and exit
+    if let (Some(mesh_path), Some(output)) = (&options.bake_sdf, &options.output) {
+        let result = ObjMesh::load(mesh_path)
+            .map_err(|err| format!("cannot load '{}': {}", mesh_path, err))
+            .and_then(|mesh| {
+                let grid = SdfGrid::bake_mesh(&mesh, options.sdf_resolution);
+                grid.save(output).map_err(|err| format!("cannot write '{}': {}", output, err))?;
+                Ok(grid)
             });
-
-            // ===========================
-            // Render the sphere
-            // ===========================
-
-            // Set the graphics pipeline for the sphere
-            render_pass.set_pipeline(&self.sphere_pipeline);
-
-            // Bind the camera data to the pipeline (view and projection matrices)
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-
-            // Set the vertex buffer containing the sphere's vertices
-            render_pass.set_vertex_buffer(0, self.sphere_vertex_buffer.slice(..));
-
-            // Set the index buffer containing the sphere's indices
-            render_pass.set_index_buffer(self.sphere_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            // Draw the sphere using indexed drawing
-            render_pass.draw_indexed(0..self.sphere_indices.len() as u32, 0, 0..1);
-
-            // ===========================
-            // Render the cloth
-            // ===========================
-
-            // Set the graphics pipeline for the cloth
-            render_pass.set_pipeline(&self.cloth_pipeline);
-
-            // Bind the texture data to the pipeline
-            render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
-
-            // Bind the camera data to the pipeline (view and projection matrices)
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-
-            // Set the vertex buffer containing the cloth's vertices
-            render_pass.set_vertex_buffer(0, self.cloth_vertex_buffer.slice(..));
-
-            // Set the index buffer containing the cloth's indices
-            render_pass.set_index_buffer(self.cloth_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            // Draw the cloth using indexed drawing
-            render_pass.draw_indexed(0..self.cloth_indices.len() as u32, 0, 0..1);
+        match result {
+            Ok(grid) => println!(
+                "bake-sdf: {} samples of a box of half extents {:?} written to '{}'",
+                grid.resolution.iter().map(u32::to_string).collect::<Vec<_>>().join(" x "),
+                grid.half_extents,
+                output,
+            ),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
-
-        // Present the rendered frame to the screen
-        frame.present();
-
-        // Return success if rendering completes without errors
-        Ok(())
+        return;
     }
 
+    // The buffers are written from Rust structs and read through WGSL structs, refuse to
+    // start if the two disagree instead of simulating garbage
+    if let Err(err) = layout::check_shader_layouts() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
-    // Function to update simulation data
-    fn update(&mut self, context: &Context, delta_time: f32) {
-        // ================================
-        // Step 1: Update uniform compute data
-        // ================================
-        let compute_data = ComputeData {
-            delta_time: delta_time / N_ITERATIONS as f32, // Divide delta_time for stability
-            nb_vertices: (N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW) as f32, // Total number of vertices
-            sphere_radius: SPHERE_RADIUS, // Sphere radius
-            sphere_center_x: SPHERE_CENTER_X, // Sphere center (X-coordinate)
-            sphere_center_y: SPHERE_CENTER_Y, // Sphere center (Y-coordinate)
-            sphere_center_z: SPHERE_CENTER_Z, // Sphere center (Z-coordinate)
-            vertex_mass: VERTEX_MASS, // Mass of each vertex
-            structural_stiffness: STRUCTURAL_STIFFNESS, // Stiffness of structural springs
-            shear_stiffness: SHEAR_STIFFNESS, // Stiffness of shear springs
-            bend_stiffness: BEND_STIFFNESS, // Stiffness of bend springs
-            structural_damping: STRUCTURAL_DAMPING, // Damping for structural springs
-            shear_damping: SHEAR_DAMPING, // Damping for shear springs
-            bend_damping: BEND_DAMPING, // Damping for bend springs
-        };
-
-        // Update the compute data buffer on the GPU
-        context.update_buffer(&self.compute_data_buffer, &[compute_data]);
-
-        // ================================
-        // Step 2: Initialize computation
-        // ================================
-        let mut computation = Computation::new(context); // Create a computation object
-
-        // ================================
-        // Step 3: Perform multiple iterations
-        // ================================
-        for _ in 0..N_ITERATIONS {
-            // --------------------------------
-            // Pass 1: Calculate forces
-            // --------------------------------
-            let mut compute_pass = computation.begin_compute_pass();
-            compute_pass.set_pipeline(&self.forces_compute_pipeline); // Use forces compute pipeline
-
-            // Bind required resources to the pipeline
-            compute_pass.set_bind_group(0, &self.compute_vertices_bind_group, &[]); // Vertex positions
-            compute_pass.set_bind_group(1, &self.compute_velocities_bind_group, &[]); // Vertex velocities
-            compute_pass.set_bind_group(2, &self.compute_data_bind_group, &[]); // Simulation parameters
-            compute_pass.set_bind_group(3, &self.springs_bind_group, &[]); // Spring connections
-
-            // Dispatch compute workgroups for force calculations
-            compute_pass.dispatch_workgroups(
-                ((N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW) as f64 / 128.0).ceil() as u32,
-                1,
-                1,
-            );
-
-            // --------------------------------
-            // Pass 2: Update positions and handle collisions
-            // --------------------------------
-            compute_pass.set_pipeline(&self.compute_pipeline); // Use position update pipeline
-
-            // Bind the same resources again for position updates
-            compute_pass.set_bind_group(0, &self.compute_vertices_bind_group, &[]);
-            compute_pass.set_bind_group(1, &self.compute_velocities_bind_group, &[]);
-            compute_pass.set_bind_group(2, &self.compute_data_bind_group, &[]);
-            compute_pass.set_bind_group(3, &self.springs_bind_group, &[]);
+    // The scene file is optional, the defaults are used without it
+    let scene = match &options.scene {
+        Some(path) => match Scene::load(path) {
+            Ok(scene) => scene,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => Scene::default(),
+    };
+
+    // Headless mode: step the simulation without a window and exit
+    if let Some(frames) = options.headless_frames {
+        let result = headless::run(&scene, frames, options.delta_time, options.backend).and_then(|report| {
+            if report.fell_back {
+                eprintln!("headless: no wgpu adapter available, fell back to the CPU solver");
+            }
+            println!("{}", report);
+            match &options.output {
+                Some(path) => headless::write_obj(path, &scene, &report.vertices),
+                None => Ok(()),
+            }
+        });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-            // Dispatch compute workgroups for position updates
-            compute_pass.dispatch_workgroups(
-                ((N_CLOTH_VERTICES_PER_ROW * N_CLOTH_VERTICES_PER_ROW) as f32 / 128.0).ceil() as u32,
-                1,
-                1,
+    // Validation mode: compare the compute shaders with the CPU solver and exit
+    if let Some(iterations) = options.validate_iterations {
+        let iteration_delta_time = options.delta_time / scene.physics.iterations as f32;
+        let result = headless::HeadlessDevice::new().and_then(|headless| {
+            println!(
+                "validate: {} iterations of {}s on '{}' ({:?}) against the CPU solver",
+                iterations, iteration_delta_time, headless.adapter_info.name, headless.adapter_info.backend,
             );
+            validate::cross_validate(&headless, &scene, iterations, iteration_delta_time)
+        });
+        match result {
+            Ok(report) => {
+                println!("{}", report);
+                if !report.passes(options.tolerance) {
+                    eprintln!("validate: divergence above the tolerance of {}", options.tolerance);
+                    std::process::exit(1);
+                }
+                println!("validate: ok, divergence below the tolerance of {}", options.tolerance);
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
-
-        // ================================
-        // Step 4: Submit computations to GPU
-        // ================================
-        computation.submit(); // Submit all compute passes for execution
+        return;
     }
 
-}
-
-fn main() {
     let window = Window::new();
 
     let context = window.get_context();
 
-    let my_app = MyApp::new(context);
+    let my_app = MyApp::new(context, &scene);
 
     window.run(my_app);
-}
\ No newline at end of file
+}
@@ -0,0 +1,425 @@
+use std::fmt;
+use std::path::Path;
+
+use wgpu_bootstrap::{
+    cgmath::{self, InnerSpace, Matrix3, Vector3},
+    default::Vertex,
+};
+
+use crate::colliders::{Contact, USE_COMPUTE_DATA_FRICTION};
+use crate::scene::Scene;
+
+// =========================================================================================
+//                                 TRIANGLE MESH COLLIDERS
+// =========================================================================================
+// Static obstacles of any shape loaded from OBJ files. The triangles of every mesh collider
+// are moved into the world once and put in a uniform grid: each cell lists the triangles
+// whose bounding box, grown by their margin, overlaps it. A vertex only tests the triangles
+// of its own cell and is pushed out of the closest one.
+//
+// The grid is built on the GPU by mesh_grid.wgsl (count, prefix sum, fill) before the first
+// step. `MeshColliders::new` computes the same grid on the CPU for the CPU solver and to size
+// the GPU buffers. Inside a cell the GPU order depends on the atomics, so the queries keep
+// the closest triangle with the smallest index and do not depend on the order.
+//
+// The meshes are surfaces, not volumes: a vertex is only detected within the margin of a
+// triangle. A vertex behind a triangle is pushed to the front side, given by the winding.
+// =========================================================================================
+
+// Maximum number of cells along each axis of the grid
+const MAX_GRID_CELLS: u32 = 64;
+
+// An OBJ file reduced to its positions and triangles
+#[derive(Clone, Debug, Default)]
+pub struct ObjMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+// A line of an OBJ file that cannot be used
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse { line: usize, reason: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "{}", err),
+            ObjError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl ObjMesh {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ObjError> {
+        let text = std::fs::read_to_string(path).map_err(ObjError::Io)?;
+        Self::parse(&text)
+    }
+
+    // Only the `v` and `f` lines are used, the polygons are split in triangle fans
+    pub fn parse(text: &str) -> Result<Self, ObjError> {
+        let mut mesh = ObjMesh::default();
+        for (i, line) in text.lines().enumerate() {
+            let parse_error = |reason: String| ObjError::Parse { line: i + 1, reason };
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let mut position = [0.0; 3];
+                    for coordinate in &mut position {
+                        let word = words.next().ok_or_else(|| parse_error(String::from("a vertex needs 3 coordinates")))?;
+                        *coordinate = word.parse().map_err(|_| parse_error(format!("invalid coordinate '{}'", word)))?;
+                    }
+                    mesh.positions.push(position);
+                }
+                Some("f") => {
+                    // `i`, `i/t`, `i//n` or `i/t/n`, negative indices count from the last vertex
+                    let mut face = Vec::new();
+                    for word in words {
+                        let index: i64 = word
+                            .split('/')
+                            .next()
+                            .and_then(|index| index.parse().ok())
+                            .ok_or_else(|| parse_error(format!("invalid face vertex '{}'", word)))?;
+                        let nb_positions = mesh.positions.len() as i64;
+                        let index = if index < 0 { nb_positions + index } else { index - 1 };
+                        if index < 0 || index >= nb_positions {
+                            return Err(parse_error(format!("vertex {} is not defined", word)));
+                        }
+                        face.push(index as u32);
+                    }
+                    if face.len() < 3 {
+                        return Err(parse_error(String::from("a face needs at least 3 vertices")));
+                    }
+                    for k in 1..face.len() - 1 {
+                        mesh.triangles.push([face[0], face[k], face[k + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(mesh)
+    }
+}
+
+// Same layout as `MeshTriangle` in compute.wgsl and mesh_grid.wgsl, in world space
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshTriangle {
+    pub a: [f32; 3],
+    pub b: [f32; 3],
+    pub c: [f32; 3],
+    pub margin: f32,
+    pub friction: f32, // negative to use the one of `ComputeData`
+}
+
+// Same layout as `MeshGrid` in compute.wgsl and mesh_grid.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshGrid {
+    pub origin_x: f32,
+    pub origin_y: f32,
+    pub origin_z: f32,
+    pub inverse_cell_size: f32, // multiplied rather than divided, so the GPU finds the same cells
+    pub cells_x: u32,
+    pub cells_y: u32,
+    pub cells_z: u32,
+    pub nb_cells: u32,
+    pub nb_triangles: u32,
+}
+
+impl MeshGrid {
+    // Cell coordinates of a point, they may be outside of the grid
+    fn cell_coordinates(&self, point: Vector3<f32>) -> [i32; 3] {
+        let origin = Vector3::new(self.origin_x, self.origin_y, self.origin_z);
+        let cell = (point - origin) * self.inverse_cell_size;
+        [cell.x.floor() as i32, cell.y.floor() as i32, cell.z.floor() as i32]
+    }
+
+    fn cell_index(&self, [x, y, z]: [u32; 3]) -> usize {
+        ((z * self.cells_y + y) * self.cells_x + x) as usize
+    }
+
+    // First and last cells overlapped by a triangle grown by its margin, clamped to the grid
+    fn triangle_cells(&self, triangle: &MeshTriangle) -> ([u32; 3], [u32; 3]) {
+        let (min, max) = triangle_bounds(triangle);
+        let first = self.cell_coordinates(min);
+        let last = self.cell_coordinates(max);
+        let dims = [self.cells_x, self.cells_y, self.cells_z];
+        let clamp = |cell: [i32; 3]| [0, 1, 2].map(|axis| cell[axis].clamp(0, dims[axis] as i32 - 1) as u32);
+        (clamp(first), clamp(last))
+    }
+}
+
+// Bounding box of a triangle grown by its margin
+fn triangle_bounds(triangle: &MeshTriangle) -> (Vector3<f32>, Vector3<f32>) {
+    let (a, b, c) = (triangle.a, triangle.b, triangle.c);
+    let min = [0, 1, 2].map(|axis| a[axis].min(b[axis]).min(c[axis]) - triangle.margin);
+    let max = [0, 1, 2].map(|axis| a[axis].max(b[axis]).max(c[axis]) + triangle.margin);
+    (min.into(), max.into())
+}
+
+// The triangles of every mesh collider of a scene and their grid
+pub struct MeshColliders {
+    pub triangles: Vec<MeshTriangle>,
+    pub grid: MeshGrid,
+    pub cell_offsets: Vec<u32>,   // the triangles of cell i are cell_triangles[cell_offsets[i]..cell_offsets[i + 1]]
+    pub cell_triangles: Vec<u32>, // sorted by triangle in every cell
+}
+
+impl MeshColliders {
+    pub fn new(scene: &Scene) -> Self {
+        // move the triangles into the world, scaled, then rotated around x, y and z, then moved
+        let mut triangles = Vec::new();
+        for config in &scene.mesh_colliders {
+            let [rx, ry, rz] = config.rotation;
+            let rotation = Matrix3::from_angle_z(cgmath::Deg(rz)) * Matrix3::from_angle_y(cgmath::Deg(ry)) * Matrix3::from_angle_x(cgmath::Deg(rx));
+            let transform = |index: u32| -> [f32; 3] {
+                let position = Vector3::from(config.mesh.positions[index as usize]);
+                (rotation * (position * config.scale) + Vector3::from(config.center)).into()
+            };
+            for &[a, b, c] in &config.mesh.triangles {
+                let triangle = MeshTriangle {
+                    a: transform(a),
+                    b: transform(b),
+                    c: transform(c),
+                    margin: config.margin,
+                    friction: config.friction.unwrap_or(USE_COMPUTE_DATA_FRICTION),
+                };
+                // a triangle without area has no normal
+                if triangle_normal(&triangle).magnitude2() > 0.0 {
+                    triangles.push(triangle);
+                }
+            }
+        }
+
+        let grid = grid_for(&triangles);
+
+        // count the triangles of every cell, prefix sum, then fill the cells in triangle order
+        let mut cell_offsets = vec![0u32; grid.nb_cells as usize + 1];
+        for triangle in &triangles {
+            let (first, last) = grid.triangle_cells(triangle);
+            for_each_cell(first, last, |cell| cell_offsets[grid.cell_index(cell) + 1] += 1);
+        }
+        for i in 0..grid.nb_cells as usize {
+            cell_offsets[i + 1] += cell_offsets[i];
+        }
+        let mut cursors = cell_offsets.clone();
+        let mut cell_triangles = vec![0u32; cell_offsets[grid.nb_cells as usize] as usize];
+        for (t, triangle) in triangles.iter().enumerate() {
+            let (first, last) = grid.triangle_cells(triangle);
+            for_each_cell(first, last, |cell| {
+                let cursor = &mut cursors[grid.cell_index(cell)];
+                cell_triangles[*cursor as usize] = t as u32;
+                *cursor += 1;
+            });
+        }
+
+        Self {
+            triangles,
+            grid,
+            cell_offsets,
+            cell_triangles,
+        }
+    }
+
+    // Same as `collide_meshes` in compute.wgsl: the position pushed out of the closest
+    // triangle within its margin, with the friction of that triangle
+    pub fn collide(&self, position: Vector3<f32>) -> Option<(Contact, f32)> {
+        let grid = &self.grid;
+        if grid.nb_triangles == 0 {
+            return None;
+        }
+        let cell = grid.cell_coordinates(position);
+        let dims = [grid.cells_x, grid.cells_y, grid.cells_z];
+        if (0..3).any(|axis| cell[axis] < 0 || cell[axis] >= dims[axis] as i32) {
+            return None;
+        }
+        let cell = grid.cell_index(cell.map(|c| c as u32));
+
+        // closest triangle, the smallest index wins the ties
+        let mut best: Option<(f32, u32, Vector3<f32>)> = None;
+        for &t in &self.cell_triangles[self.cell_offsets[cell] as usize..self.cell_offsets[cell + 1] as usize] {
+            let triangle = &self.triangles[t as usize];
+            let closest = closest_point_on_triangle(position, triangle);
+            let distance = (position - closest).magnitude();
+            let closer = match best {
+                Some((best_distance, best_t, _)) => distance < best_distance || (distance == best_distance && t < best_t),
+                None => true,
+            };
+            if distance < triangle.margin && closer {
+                best = Some((distance, t, closest));
+            }
+        }
+
+        let (distance, t, closest) = best?;
+        let triangle = &self.triangles[t as usize];
+        let face_normal = triangle_normal(triangle).normalize();
+        let offset = position - closest;
+        // on the front side the vertex moves away from the closest point, behind the
+        // triangle it is moved to the front side
+        let normal = if distance > 0.0 && offset.dot(face_normal) >= 0.0 { offset / distance } else { face_normal };
+        Some((
+            Contact {
+                position: closest + normal * triangle.margin,
+                normal,
+            },
+            triangle.friction,
+        ))
+    }
+
+    // Contents of the triangles buffer, a storage buffer cannot be empty so a triangle is
+    // added when there is none (it is not used, the grid has no triangle)
+    pub fn buffer_contents(&self) -> Vec<MeshTriangle> {
+        if self.triangles.is_empty() {
+            return vec![MeshTriangle {
+                a: [0.0; 3],
+                b: [0.0; 3],
+                c: [0.0; 3],
+                margin: 0.0,
+                friction: USE_COMPUTE_DATA_FRICTION,
+            }];
+        }
+        self.triangles.clone()
+    }
+
+    // Flat shaded triangles of every mesh collider, already in world space
+    pub fn render_mesh(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        for triangle in &self.triangles {
+            let normal = triangle_normal(triangle).normalize();
+            for position in [triangle.a, triangle.b, triangle.c] {
+                vertices.push(Vertex {
+                    position,
+                    normal: normal.into(),
+                    tangent: [0.0, 0.0, 0.0],
+                    tex_coords: [0.0, 0.0],
+                });
+            }
+        }
+        let indices = (0..vertices.len() as u32).collect();
+        (vertices, indices)
+    }
+}
+
+// Cells of the bounding box of all the triangles, at most MAX_GRID_CELLS along each axis,
+// with cells about twice as large as the triangles
+fn grid_for(triangles: &[MeshTriangle]) -> MeshGrid {
+    if triangles.is_empty() {
+        // a single unused cell, the buffers cannot be empty
+        return MeshGrid {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            origin_z: 0.0,
+            inverse_cell_size: 1.0,
+            cells_x: 1,
+            cells_y: 1,
+            cells_z: 1,
+            nb_cells: 1,
+            nb_triangles: 0,
+        };
+    }
+
+    let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+    let mut size_sum = 0.0;
+    for triangle in triangles {
+        let (triangle_min, triangle_max) = triangle_bounds(triangle);
+        for axis in 0..3 {
+            min[axis] = min[axis].min(triangle_min[axis]);
+            max[axis] = max[axis].max(triangle_max[axis]);
+        }
+        let size = triangle_max - triangle_min;
+        size_sum += size.x.max(size.y).max(size.z);
+    }
+    let extent = max - min;
+    let largest_extent = extent.x.max(extent.y).max(extent.z);
+    let cell_size = (2.0 * size_sum / triangles.len() as f32).max(largest_extent / MAX_GRID_CELLS as f32);
+    let inverse_cell_size = 1.0 / cell_size;
+    let cells = [0, 1, 2].map(|axis| ((extent[axis] * inverse_cell_size).ceil() as u32).clamp(1, MAX_GRID_CELLS));
+
+    MeshGrid {
+        origin_x: min.x,
+        origin_y: min.y,
+        origin_z: min.z,
+        inverse_cell_size,
+        cells_x: cells[0],
+        cells_y: cells[1],
+        cells_z: cells[2],
+        nb_cells: cells[0] * cells[1] * cells[2],
+        nb_triangles: triangles.len() as u32,
+    }
+}
+
+fn for_each_cell(first: [u32; 3], last: [u32; 3], mut f: impl FnMut([u32; 3])) {
+    for z in first[2]..=last[2] {
+        for y in first[1]..=last[1] {
+            for x in first[0]..=last[0] {
+                f([x, y, z]);
+            }
+        }
+    }
+}
+
+// Not normalized, its length is twice the area
+fn triangle_normal(triangle: &MeshTriangle) -> Vector3<f32> {
+    let a = Vector3::from(triangle.a);
+    (Vector3::from(triangle.b) - a).cross(Vector3::from(triangle.c) - a)
+}
+
+// Closest point of a triangle, from "Real-Time Collision Detection" (Ericson), same as
+// `closest_point_on_triangle` in compute.wgsl
+pub fn closest_point_on_triangle(p: Vector3<f32>, triangle: &MeshTriangle) -> Vector3<f32> {
+    let (a, b, c) = (Vector3::from(triangle.a), Vector3::from(triangle.b), Vector3::from(triangle.c));
+    let ab = b - a;
+    let ac = c - a;
+
+    // vertex region of a
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    // vertex region of b
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    // edge region of ab
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    // vertex region of c
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    // edge region of ac
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    // edge region of bc
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // inside the face
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
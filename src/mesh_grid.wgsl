@@ -0,0 +1,105 @@
+// Compute shader building the uniform grid of the mesh colliders (see mesh.rs)
+// 1. count_main: every triangle counts itself in the cells overlapped by its bounding box
+// 2. scan_main:  a single thread turns the counts into the offsets of the cells
+// 3. fill_main:  every triangle writes its index in its cells, the counts are used as cursors
+//                and must be cleared before this pass
+
+// A triangle of a mesh collider, in world space
+struct MeshTriangle {
+    a_x: f32, // First corner
+    a_y: f32,
+    a_z: f32,
+    b_x: f32, // Second corner
+    b_y: f32,
+    b_z: f32,
+    c_x: f32, // Third corner
+    c_y: f32,
+    c_z: f32,
+    margin: f32,   // Distance kept between the cloth and the triangle
+    friction: f32, // Coulomb friction coefficient, negative to use data.friction
+}
+
+// Uniform grid covering all the triangles grown by their margin
+struct MeshGrid {
+    origin_x: f32,          // Corner of the first cell
+    origin_y: f32,
+    origin_z: f32,
+    inverse_cell_size: f32, // 1 / size of the cubic cells
+    cells_x: u32,           // Number of cells along each axis
+    cells_y: u32,
+    cells_z: u32,
+    nb_cells: u32,
+    nb_triangles: u32,
+}
+
+@group(0) @binding(0) var<uniform> grid: MeshGrid;
+@group(0) @binding(1) var<storage, read> meshTriangles: array<MeshTriangle>;
+@group(0) @binding(2) var<storage, read_write> cellCounts: array<atomic<u32>>; // Triangles per cell, then cursors
+@group(0) @binding(3) var<storage, read_write> cellOffsets: array<u32>; // nb_cells + 1 offsets
+@group(0) @binding(4) var<storage, read_write> cellTriangles: array<u32>; // Triangles of every cell
+
+// Cell coordinates of a point, clamped to the grid (same as MeshGrid::cell_coordinates)
+fn clamped_cell(point: vec3<f32>) -> vec3<u32> {
+    let origin = vec3<f32>(grid.origin_x, grid.origin_y, grid.origin_z);
+    let cell = vec3<i32>(floor((point - origin) * grid.inverse_cell_size));
+    let last = vec3<i32>(i32(grid.cells_x) - 1, i32(grid.cells_y) - 1, i32(grid.cells_z) - 1);
+    return vec3<u32>(clamp(cell, vec3<i32>(0), last));
+}
+
+// First and last cells overlapped by a triangle grown by its margin
+fn triangle_cells(triangle: MeshTriangle) -> array<vec3<u32>, 2> {
+    let a = vec3<f32>(triangle.a_x, triangle.a_y, triangle.a_z);
+    let b = vec3<f32>(triangle.b_x, triangle.b_y, triangle.b_z);
+    let c = vec3<f32>(triangle.c_x, triangle.c_y, triangle.c_z);
+    let low = min(min(a, b), c) - vec3<f32>(triangle.margin);
+    let high = max(max(a, b), c) + vec3<f32>(triangle.margin);
+    return array<vec3<u32>, 2>(clamped_cell(low), clamped_cell(high));
+}
+
+fn cell_index(cell: vec3<u32>) -> u32 {
+    return (cell.z * grid.cells_y + cell.y) * grid.cells_x + cell.x;
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn count_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= grid.nb_triangles) {
+        return;
+    }
+    let cells = triangle_cells(meshTriangles[param.x]);
+    for (var z = cells[0].z; z <= cells[1].z; z++) {
+        for (var y = cells[0].y; y <= cells[1].y; y++) {
+            for (var x = cells[0].x; x <= cells[1].x; x++) {
+                atomicAdd(&cellCounts[cell_index(vec3<u32>(x, y, z))], 1u);
+            }
+        }
+    }
+}
+
+// Sequential prefix sum, the grid is built once so one thread is enough
+@compute @workgroup_size(1, 1, 1)
+fn scan_main() {
+    cellOffsets[0] = 0u;
+    for (var i = 0u; i < grid.nb_cells; i++) {
+        cellOffsets[i + 1u] = cellOffsets[i] + atomicLoad(&cellCounts[i]);
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn fill_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= grid.nb_triangles) {
+        return;
+    }
+    let cells = triangle_cells(meshTriangles[param.x]);
+    for (var z = cells[0].z; z <= cells[1].z; z++) {
+        for (var y = cells[0].y; y <= cells[1].y; y++) {
+            for (var x = cells[0].x; x <= cells[1].x; x++) {
+                let cell = cell_index(vec3<u32>(x, y, z));
+                let slot = cellOffsets[cell] + atomicAdd(&cellCounts[cell], 1u);
+                // Never write outside of the cell, even if the counts were wrong
+                if (slot < cellOffsets[cell + 1u]) {
+                    cellTriangles[slot] = param.x;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use wgpu_bootstrap::cgmath::Vector3;
+
+use crate::scene::{Keyframe, SphereConfig};
+
+// =========================================================================================
+//                                   SPHERE MOTION
+// =========================================================================================
+// The sphere follows its keyframes (linear interpolation, optionally looping) or moves at a
+// constant velocity. A displacement can be added on top of the trajectory, the demo uses it
+// to drag the sphere with the mouse. The velocity of the sphere is given to the collision
+// response so that the cloth touching it is carried along.
+// =========================================================================================
+
+pub struct SphereMotion {
+    start: Vector3<f32>,
+    velocity: Vector3<f32>,
+    keyframes: Vec<Keyframe>,
+    looping: bool,
+    time: f32,
+    offset: Vector3<f32>,         // displacement added on top of the trajectory
+    pending_offset: Vector3<f32>, // displacement applied at the next `advance`
+}
+
+impl SphereMotion {
+    pub fn new(sphere: &SphereConfig) -> Self {
+        Self {
+            start: Vector3::from(sphere.center),
+            velocity: Vector3::from(sphere.velocity),
+            keyframes: sphere.keyframes.clone(),
+            looping: sphere.looping,
+            time: 0.0,
+            offset: Vector3::new(0.0, 0.0, 0.0),
+            pending_offset: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Does the sphere ever move on its own
+    pub fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty() || self.velocity != Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    // Move the sphere by `offset` during the next frame
+    pub fn push(&mut self, offset: Vector3<f32>) {
+        self.pending_offset += offset;
+    }
+
+    // Advance the time by `delta_time` and return the new center and the velocity of the
+    // sphere during this frame
+    pub fn advance(&mut self, delta_time: f32) -> (Vector3<f32>, Vector3<f32>) {
+        self.time += delta_time;
+        self.offset += self.pending_offset;
+        let (center, velocity) = self.trajectory(self.time);
+        let velocity = velocity + self.pending_offset / delta_time;
+        self.pending_offset = Vector3::new(0.0, 0.0, 0.0);
+        (center + self.offset, velocity)
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        self.trajectory(self.time).0 + self.offset
+    }
+
+    // Center and velocity of the scripted trajectory at `time`
+    fn trajectory(&self, time: f32) -> (Vector3<f32>, Vector3<f32>) {
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        let (first, last) = match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (self.start + self.velocity * time, self.velocity),
+        };
+
+        let mut time = time;
+        if self.looping && last.time > first.time {
+            time = first.time + (time - first.time).rem_euclid(last.time - first.time);
+        }
+        if time <= first.time {
+            return (Vector3::from(first.center), zero);
+        }
+        if time >= last.time {
+            return (Vector3::from(last.center), zero);
+        }
+
+        // the scene validation guarantees that the times are increasing
+        let next = self.keyframes.iter().position(|keyframe| keyframe.time > time).unwrap_or(self.keyframes.len() - 1);
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let (center_a, center_b) = (Vector3::from(a.center), Vector3::from(b.center));
+        let velocity = (center_b - center_a) / (b.time - a.time);
+        (center_a + velocity * (time - a.time), velocity)
+    }
+}
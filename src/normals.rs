@@ -0,0 +1,102 @@
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector2, Vector3},
+    default::Vertex,
+};
+
+// =========================================================================================
+//                                   SMOOTH NORMALS
+// =========================================================================================
+// After the integration the vertices have moved but their normals and tangents are still
+// the ones of the flat cloth. normals.wgsl recomputes them from the deformed triangles:
+// every vertex averages the normals and tangents of the triangles around it, listed in a
+// CSR layout like the springs so that every thread only writes its own vertex.
+// =========================================================================================
+
+// The triangles around one vertex: vertex_triangles[offset..offset + count]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TriangleRange {
+    pub offset: u32,
+    pub count: u32,
+}
+
+pub struct TriangleAdjacency {
+    pub triangles: Vec<u32>, // 3 vertex indices per triangle
+    pub ranges: Vec<TriangleRange>,
+    pub vertex_triangles: Vec<u32>,
+}
+
+impl TriangleAdjacency {
+    // `indices` is a triangle list, all of them must be below nb_vertices
+    pub fn new(nb_vertices: u32, indices: &[u32]) -> Self {
+        let mut per_vertex: Vec<Vec<u32>> = vec![Vec::new(); nb_vertices as usize];
+        for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+            for &vertex in vertices {
+                per_vertex[vertex as usize].push(triangle as u32);
+            }
+        }
+
+        let mut vertex_triangles = Vec::with_capacity(indices.len());
+        let mut ranges = Vec::with_capacity(nb_vertices as usize);
+        for triangles in per_vertex {
+            ranges.push(TriangleRange {
+                offset: vertex_triangles.len() as u32,
+                count: triangles.len() as u32,
+            });
+            vertex_triangles.extend(triangles);
+        }
+
+        let mut triangles = indices[..indices.len() / 3 * 3].to_vec();
+        // wgpu does not accept empty storage buffers
+        if triangles.is_empty() {
+            triangles.push(0);
+        }
+        if vertex_triangles.is_empty() {
+            vertex_triangles.push(0);
+        }
+
+        Self {
+            triangles,
+            ranges,
+            vertex_triangles,
+        }
+    }
+}
+
+// Same as normals.wgsl: area weighted smooth normals and tangents along u
+pub fn compute_normals(vertices: &mut [Vertex], adjacency: &TriangleAdjacency) {
+    let position = |vertices: &[Vertex], index: u32| Vector3::from(vertices[index as usize].position);
+    let tex_coords = |vertices: &[Vertex], index: u32| Vector2::from(vertices[index as usize].tex_coords);
+
+    for (vertex, range) in adjacency.ranges.iter().enumerate() {
+        let mut normal_sum = Vector3::new(0.0, 0.0, 0.0);
+        let mut tangent_sum = Vector3::new(0.0, 0.0, 0.0);
+
+        for &triangle in &adjacency.vertex_triangles[range.offset as usize..(range.offset + range.count) as usize] {
+            let triangle = &adjacency.triangles[triangle as usize * 3..triangle as usize * 3 + 3];
+
+            let edge_1 = position(vertices, triangle[1]) - position(vertices, triangle[0]);
+            let edge_2 = position(vertices, triangle[2]) - position(vertices, triangle[0]);
+            normal_sum += edge_1.cross(edge_2);
+
+            let delta_uv_1 = tex_coords(vertices, triangle[1]) - tex_coords(vertices, triangle[0]);
+            let delta_uv_2 = tex_coords(vertices, triangle[2]) - tex_coords(vertices, triangle[0]);
+            let r = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+            if r != 0.0 {
+                tangent_sum += (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) / r;
+            }
+        }
+
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        if normal_sum.magnitude() > 0.0 {
+            normal = normal_sum.normalize();
+        }
+        let mut tangent = tangent_sum - normal * normal.dot(tangent_sum);
+        if tangent.magnitude() > 0.0 {
+            tangent = tangent.normalize();
+        }
+
+        vertices[vertex].normal = normal.into();
+        vertices[vertex].tangent = tangent.into();
+    }
+}
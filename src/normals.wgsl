@@ -0,0 +1,89 @@
+// Normals compute shader, run once per frame after the integration
+
+// Structure to store positions and related attributes of each vertex
+struct Position {
+    position_x: f32, // X-coordinate of the vertex position
+    position_y: f32, // Y-coordinate of the vertex position
+    position_z: f32, // Z-coordinate of the vertex position
+    normal_x: f32,   // X-component of the vertex normal, written here
+    normal_y: f32,   // Y-component of the vertex normal, written here
+    normal_z: f32,   // Z-component of the vertex normal, written here
+    tangent_x: f32,  // X-component of the vertex tangent, written here
+    tangent_y: f32,  // Y-component of the vertex tangent, written here
+    tangent_z: f32,  // Z-component of the vertex tangent, written here
+    tex_coords_x: f32, // X-coordinate of the texture
+    tex_coords_y: f32, // Y-coordinate of the texture
+}
+
+// The triangles around a vertex are vertexTriangles[offset..offset + count]
+struct TriangleRange {
+    offset: u32,
+    count: u32,
+}
+
+// Buffers and data bindings
+@group(0) @binding(0) var<storage, read_write> verticiesPositions: array<Position>; // Vertices of the cloth
+@group(1) @binding(0) var<storage, read> triangles: array<u32>; // 3 vertex indices per triangle
+@group(1) @binding(1) var<storage, read> triangleRanges: array<TriangleRange>; // One range per vertex
+@group(1) @binding(2) var<storage, read> vertexTriangles: array<u32>; // Triangles around every vertex
+
+fn position(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+}
+
+fn tex_coords(index: u32) -> vec2<f32> {
+    return vec2<f32>(verticiesPositions[index].tex_coords_x, verticiesPositions[index].tex_coords_y);
+}
+
+// Compute shader entry point
+@compute @workgroup_size(128, 1, 1)
+fn main(@builtin(global_invocation_id) param: vec3<u32>) {
+    // One thread per vertex, there is one triangle range per vertex
+    if (param.x >= arrayLength(&triangleRanges)) {
+        return;
+    }
+
+    var normal_sum = vec3<f32>(0.0, 0.0, 0.0);
+    var tangent_sum = vec3<f32>(0.0, 0.0, 0.0);
+
+    // Loop through all the triangles using the current vertex
+    let range = triangleRanges[param.x];
+    for (var i = range.offset; i < range.offset + range.count; i++) {
+        let triangle = vertexTriangles[i];
+        let index_0 = triangles[triangle * 3u];
+        let index_1 = triangles[triangle * 3u + 1u];
+        let index_2 = triangles[triangle * 3u + 2u];
+
+        // The cross product of two edges is the face normal scaled by twice the area,
+        // so bigger triangles weigh more in the smooth normal
+        let edge_1 = position(index_1) - position(index_0);
+        let edge_2 = position(index_2) - position(index_0);
+        normal_sum += cross(edge_1, edge_2);
+
+        // Tangent along the u texture coordinate
+        let delta_uv_1 = tex_coords(index_1) - tex_coords(index_0);
+        let delta_uv_2 = tex_coords(index_2) - tex_coords(index_0);
+        let r = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+        if (r != 0.0) {
+            tangent_sum += (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) / r;
+        }
+    }
+
+    // normalize() of a zero vector is NaN, keep zero for isolated or degenerate vertices
+    var normal = vec3<f32>(0.0, 0.0, 0.0);
+    if (length(normal_sum) > 0.0) {
+        normal = normalize(normal_sum);
+    }
+    // Make the tangent perpendicular to the normal (Gram-Schmidt)
+    var tangent = tangent_sum - normal * dot(normal, tangent_sum);
+    if (length(tangent) > 0.0) {
+        tangent = normalize(tangent);
+    }
+
+    verticiesPositions[param.x].normal_x = normal.x;
+    verticiesPositions[param.x].normal_y = normal.y;
+    verticiesPositions[param.x].normal_z = normal.z;
+    verticiesPositions[param.x].tangent_x = tangent.x;
+    verticiesPositions[param.x].tangent_y = tangent.y;
+    verticiesPositions[param.x].tangent_z = tangent.z;
+}
@@ -0,0 +1,418 @@
+use wgpu_bootstrap::{
+    context::Context,
+    default::Vertex,
+    frame::Frame,
+    texture::create_texture_bind_group,
+    wgpu,
+};
+
+use crate::camera::OrbitCamera;
+use crate::cloth::Indices;
+use crate::colliders::collider_mesh;
+use crate::layout;
+use crate::light::LightUniform;
+use crate::mesh::MeshColliders;
+use crate::scene::Scene;
+use crate::simulation::ClothSimulation;
+
+// =========================================================================================
+//                                    SCENE RENDERER
+// =========================================================================================
+// Draws a `ClothSimulation` in a window: the ground as a grid, the colliders shaded with an
+// optional wireframe overlay, then the textured cloth, all lit by the light of the scene and
+// seen from an `OrbitCamera`. The simulation advances by fixed steps, so the colliders are
+// drawn between their positions before and after the last step like the cloth
+// (`save_collider_models` before every step, then `update` with the same alpha as
+// `ClothSimulation::interpolate`).
+// =========================================================================================
+
+// Half size of the square drawn for the ground, the grid fades out before its edges
+const GROUND_DRAW_SIZE: f32 = 500.0;
+
+pub struct SceneRenderer {
+    camera_buffer: wgpu::Buffer, // rewritten every frame from the camera
+    camera_bind_group: wgpu::BindGroup,
+    texture_bind_group: wgpu::BindGroup,
+    // colliders, the sphere of the scene first
+    collider_pipeline: wgpu::RenderPipeline,
+    collider_wireframe_pipeline: wgpu::RenderPipeline,
+    collider_meshes: Vec<ColliderMesh>, // same order as `simulation.colliders()`, without the ground
+    mesh_colliders: Option<ColliderMesh>, // every triangle of the mesh colliders, in world space
+    ground: Option<GroundMesh>, // drawn as a grid instead of a collider
+    pub show_wireframe: bool,
+    // cloth
+    cloth_pipeline: wgpu::RenderPipeline,
+    // light
+    light_uniform: LightUniform,
+    light_buffer: wgpu::Buffer, // the camera position is rewritten every frame
+    light_bind_group: wgpu::BindGroup,
+}
+
+impl SceneRenderer {
+    pub fn new(context: &Context, scene: &Scene, simulation: &ClothSimulation, camera: &OrbitCamera) -> Self {
+        let texture = context.create_texture(
+            "English",
+            include_bytes!("texture.jpg"),
+        );
+
+        let texture_bind_group = create_texture_bind_group(context, &texture);
+
+        // =====================================================================
+        //                              Camera
+        // =====================================================================
+        // its uniform is rewritten in `update`
+        let camera_buffer = context.create_buffer(
+            &[camera.uniform(context.get_aspect_ratio())],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        );
+        let camera_bind_group = context.create_bind_group(
+            "Camera Bind Group",
+            &context.camera_bind_group_layout,
+            &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        );
+
+        // =====================================================================
+        //                              Light
+        // =====================================================================
+        let light_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Light Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        // the camera position is only needed for the specular highlight
+        let light_uniform = LightUniform::new(&scene.light, scene.camera.eye);
+        let light_buffer = context.create_buffer(
+            &[light_uniform],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        );
+        let light_bind_group = context.create_bind_group(
+            "Light Bind Group",
+            &light_bind_group_layout,
+            &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            }],
+        );
+
+        // =====================================================================
+        //                              Colliders
+        // =====================================================================
+        // the meshes are built in the local frame of the colliders, the model matrix places them
+        let model_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Model Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        // shaded with the same light as the cloth
+        let collider_pipeline = context.create_render_pipeline(
+            "Render Pipeline Collider",
+            layout::COLLIDER_SHADER,
+            &[Vertex::desc()],
+            &[&context.camera_bind_group_layout, &light_bind_group_layout, &model_bind_group_layout],
+            wgpu::PrimitiveTopology::TriangleList
+        );
+        // debug overlay drawn on top of the shaded colliders
+        let collider_wireframe_pipeline = context.create_render_pipeline(
+            "Render Pipeline Collider Wireframe",
+            include_str!("wireframe.wgsl"),
+            &[Vertex::desc()],
+            &[&context.camera_bind_group_layout, &model_bind_group_layout],
+            wgpu::PrimitiveTopology::LineList
+        );
+
+        // one mesh per collider, with its own model matrix, the ground is the last collider
+        let nb_drawn_colliders = simulation.colliders().len() - scene.ground.enabled as usize;
+        let collider_meshes = simulation.colliders()[..nb_drawn_colliders]
+            .iter()
+            .map(|collider| {
+                let (vertices, indices) = collider_mesh(collider, simulation.sdf_atlas());
+                ColliderMesh::new(context, &model_bind_group_layout, &vertices, indices, collider.model_matrix())
+            })
+            .collect();
+
+        // the triangles of the mesh colliders are already in world space, they are drawn like
+        // the other colliders with an identity model matrix
+        let mesh_colliders = (!scene.mesh_colliders.is_empty()).then(|| {
+            let (vertices, indices) = MeshColliders::new(scene).render_mesh();
+            let identity = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+            ColliderMesh::new(context, &model_bind_group_layout, &vertices, indices, identity)
+        });
+
+        // =====================================================================
+        //                              Ground
+        // =====================================================================
+        // a large square at the ground height, the grid is drawn by the fragment shader
+        let ground = scene.ground.enabled.then(|| {
+            let pipeline = context.create_render_pipeline(
+                "Render Pipeline Ground",
+                layout::GROUND_SHADER,
+                &[Vertex::desc()],
+                &[&context.camera_bind_group_layout, &light_bind_group_layout],
+                wgpu::PrimitiveTopology::TriangleList
+            );
+            let (s, y) = (GROUND_DRAW_SIZE, scene.ground.height);
+            let corner = |x: f32, z: f32| Vertex {
+                position: [x, y, z],
+                normal: [0.0, 1.0, 0.0],
+                tangent: [1.0, 0.0, 0.0],
+                tex_coords: [0.0, 0.0],
+            };
+            // two triangles facing up
+            let vertices = [
+                corner(-s, -s), corner(-s, s), corner(s, s),
+                corner(-s, -s), corner(s, s), corner(s, -s),
+            ];
+            GroundMesh {
+                pipeline,
+                vertex_buffer: context.create_buffer(&vertices, wgpu::BufferUsages::VERTEX),
+            }
+        });
+
+        // =====================================================================
+        //                              Cloth
+        // =====================================================================
+        let cloth_pipeline = context.create_render_pipeline(
+            "Render Pipeline Cloth",
+            layout::CLOTH_SHADER,
+            &[Vertex::desc()],
+            &[
+                &context.texture_bind_group_layout,
+                &context.camera_bind_group_layout,
+                &light_bind_group_layout,
+                ],
+            wgpu::PrimitiveTopology::TriangleList
+        );
+
+        Self {
+            camera_buffer,
+            camera_bind_group,
+            texture_bind_group,
+            // colliders
+            collider_pipeline,
+            collider_wireframe_pipeline,
+            collider_meshes,
+            mesh_colliders,
+            ground,
+            show_wireframe: scene.sphere.wireframe,
+            // cloth
+            cloth_pipeline,
+            // light
+            light_uniform,
+            light_buffer,
+            light_bind_group,
+        }
+    }
+
+    // Keep where the colliders are before a step, they are drawn between there and where
+    // they are after it
+    pub fn save_collider_models(&mut self, simulation: &ClothSimulation) {
+        for (mesh, collider) in self.collider_meshes.iter_mut().zip(simulation.colliders()) {
+            mesh.previous_model = collider.model_matrix();
+        }
+    }
+
+    // Rewrite the camera and light uniforms, and place the colliders at the fraction `alpha`
+    // of the last step, the one given to `ClothSimulation::interpolate`
+    pub fn update(&mut self, context: &Context, camera: &OrbitCamera, simulation: &ClothSimulation, alpha: f32) {
+        // the aspect ratio changes when the window is resized
+        context.update_buffer(&self.camera_buffer, &[camera.uniform(context.get_aspect_ratio())]);
+        self.light_uniform.set_camera_position(camera.eye().into());
+        context.update_buffer(&self.light_buffer, &[self.light_uniform]);
+
+        for (mesh, collider) in self.collider_meshes.iter().zip(simulation.colliders()) {
+            // the colliders only move by translation, their matrices can be blended term by term
+            let mut model = collider.model_matrix();
+            for (column, previous) in model.iter_mut().zip(mesh.previous_model) {
+                for (value, previous) in column.iter_mut().zip(previous) {
+                    *value = previous + alpha * (*value - previous);
+                }
+            }
+            context.update_buffer(&mesh.model_buffer, &[ModelUniform { model }]);
+        }
+    }
+
+    // Draw the ground, the colliders and the interpolated cloth of `simulation`
+    pub fn render(&self, context: &Context, simulation: &ClothSimulation) -> Result<(), wgpu::SurfaceError> {
+        // Create a new frame to render into
+        let mut frame = Frame::new(context)?;
+
+        {
+            // Begin a new render pass with a background color (light gray)
+            let mut render_pass = frame.begin_render_pass(wgpu::Color {
+                r: 0.85, // Red component of the background color
+                g: 0.85, // Green component of the background color
+                b: 0.85, // Blue component of the background color
+                a: 1.0,  // Alpha (opacity) set to fully opaque
+            });
+
+            // ===========================
+            // Render the ground
+            // ===========================
+
+            if let Some(ground) = &self.ground {
+                render_pass.set_pipeline(&ground.pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_bind_group(1, &self.light_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, ground.vertex_buffer.slice(..));
+                render_pass.draw(0..6, 0..1);
+            }
+
+            // ===========================
+            // Render the colliders
+            // ===========================
+
+            for mesh in self.collider_meshes.iter().chain(&self.mesh_colliders) {
+                // Set the graphics pipeline for the colliders
+                render_pass.set_pipeline(&self.collider_pipeline);
+
+                // Bind the camera data to the pipeline (view and projection matrices)
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+
+                // Bind the light data to the pipeline
+                render_pass.set_bind_group(1, &self.light_bind_group, &[]);
+
+                // Bind the model matrix placing the collider
+                render_pass.set_bind_group(2, &mesh.model_bind_group, &[]);
+
+                // Set the vertex buffer containing the collider's vertices
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+                // Set the index buffer containing the collider's indices
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.indices.format());
+
+                // Draw the collider using indexed drawing
+                render_pass.draw_indexed(0..mesh.indices.len() as u32, 0, 0..1);
+
+                // Draw the edges of the collider on top of it for debugging
+                if self.show_wireframe {
+                    render_pass.set_pipeline(&self.collider_wireframe_pipeline);
+                    render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                    render_pass.set_bind_group(1, &mesh.model_bind_group, &[]);
+                    render_pass.set_index_buffer(mesh.line_index_buffer.slice(..), mesh.line_indices.format());
+                    render_pass.draw_indexed(0..mesh.line_indices.len() as u32, 0, 0..1);
+                }
+            }
+
+            // ===========================
+            // Render the cloth
+            // ===========================
+
+            // Set the graphics pipeline for the cloth
+            render_pass.set_pipeline(&self.cloth_pipeline);
+
+            // Bind the texture data to the pipeline
+            render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
+
+            // Bind the camera data to the pipeline (view and projection matrices)
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+
+            // Bind the light data to the pipeline
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+
+            // Set the vertex buffer containing the cloth's vertices, interpolated between the last two steps
+            render_pass.set_vertex_buffer(0, simulation.render_vertex_buffer().slice(..));
+
+            // Set the index buffer containing the cloth's indices
+            render_pass.set_index_buffer(simulation.index_buffer().slice(..), simulation.index_format());
+
+            // Draw the cloth using indexed drawing
+            render_pass.draw_indexed(0..simulation.nb_indices(), 0, 0..1);
+        }
+
+        // Present the rendered frame to the screen
+        frame.present();
+
+        // Return success if rendering completes without errors
+        Ok(())
+    }
+}
+
+// The ground plane, drawn as a grid
+struct GroundMesh {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer, // two triangles, already in world space
+}
+
+// Same layout as `Model` in collider.wgsl and wireframe.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ModelUniform {
+    model: [[f32; 4]; 4],
+}
+
+// The buffers drawing one collider
+struct ColliderMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    indices: Indices,
+    line_index_buffer: wgpu::Buffer, // Every edge of the mesh once, for the wireframe
+    line_indices: Indices,
+    model_buffer: wgpu::Buffer, // rewritten every frame, the sphere can move
+    model_bind_group: wgpu::BindGroup,
+    previous_model: [[f32; 4]; 4], // before the last step, for the interpolation
+}
+
+impl ColliderMesh {
+    fn new(context: &Context, model_bind_group_layout: &wgpu::BindGroupLayout, vertices: &[Vertex], indices: Vec<u32>, model: [[f32; 4]; 4]) -> Self {
+        let nb_vertices = vertices.len() as u32;
+        let line_indices = Indices::new(wireframe_indices(&indices), nb_vertices)
+            .expect("the collider mesh indices are always in range");
+        let indices = Indices::new(indices, nb_vertices)
+            .expect("the collider mesh indices are always in range");
+        let model_buffer = context.create_buffer(
+            &[ModelUniform { model }],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        );
+        let model_bind_group = context.create_bind_group(
+            "Collider Model Bind Group",
+            model_bind_group_layout,
+            &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: model_buffer.as_entire_binding(),
+            }],
+        );
+        ColliderMesh {
+            vertex_buffer: context.create_buffer(vertices, wgpu::BufferUsages::VERTEX),
+            index_buffer: context.create_buffer(indices.as_bytes(), wgpu::BufferUsages::INDEX),
+            line_index_buffer: context.create_buffer(line_indices.as_bytes(), wgpu::BufferUsages::INDEX),
+            indices,
+            line_indices,
+            model_buffer,
+            model_bind_group,
+            previous_model: model,
+        }
+    }
+}
+
+// Every edge of a triangle list once, as a line list
+fn wireframe_indices(triangles: &[u32]) -> Vec<u32> {
+    let mut edges = std::collections::BTreeSet::new();
+    for triangle in triangles.chunks_exact(3) {
+        for k in 0..3 {
+            let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+            edges.insert((a.min(b), a.max(b)));
+        }
+    }
+    edges.into_iter().flat_map(|(a, b)| [a, b]).collect()
+}
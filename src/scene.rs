@@ -0,0 +1,676 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::colliders::Collider;
+use crate::mesh::{ObjError, ObjMesh};
+use crate::sdf::{SdfError, SdfGrid, MAX_ATLAS_DEPTH, MAX_SDF_RESOLUTION};
+
+// =========================================================================================
+//                                     SCENE FILE
+// =========================================================================================
+// A scene is loaded from a TOML file given on the command line. Every section and every
+// field is optional: anything missing falls back to the values of `Scene::default()`,
+// which are the parameters the demo has always used.
+//
+// [cloth]
+// width = 50.0
+// height = 50.0
+// resolution = [25, 25]
+// pins = [[0, 0], [24, 0]]    # grid coordinates (along the width, along the height)
+// pinned_edges = ["z_min"]    # x_min, x_max, z_min or z_max
+// center = [0.0, 10.5, 0.0]
+//
+// [sphere]
+// enabled = true    # false removes the sphere, the other colliders stay
+// radius = 10.0
+// center = [0.0, 0.0, 0.0]
+// margin = 0.5      # distance kept between the cloth and the surface
+// wireframe = false # draw the edges of the sphere on top of it
+// velocity = [0.0, 0.0, 0.0] # constant velocity, or:
+// keyframes = [{ time = 0.0, center = [0.0, 0.0, 0.0] }, { time = 2.0, center = [10.0, 0.0, 0.0] }]
+// looping = true    # start the keyframes again after the last one
+//
+// [[colliders]]     # any number of static colliders
+// shape = "box"     # sphere, box, capsule, plane or sdf
+// center = [0.0, 5.0, 0.0]
+// rotation = [0.0, 45.0, 0.0] # degrees around x, then y, then z, a box without rotation is axis aligned
+// half_extents = [10.0, 1.0, 10.0] # box
+// radius = 2.0      # sphere and capsule
+// half_height = 5.0 # capsule: half length of the segment between its two half spheres, along y
+// margin = 0.5      # distance kept between the cloth and the surface
+// friction = 0.8    # optional, instead of physics.friction
+//                   # a plane goes through the center, the cloth stays on the side of its y axis
+// path = "meshes/torus.sdf" # sdf: a field baked with --bake-sdf, relative to the scene file, or:
+// primitives = [{ shape = "sphere", radius = 2.0 }, { shape = "box", center = [0.0, -2.0, 0.0], half_extents = [1.0, 2.0, 1.0] }]
+// half_extents = [4.0, 5.0, 4.0] # sdf from primitives: the box sampled around the center
+// resolution = [32, 32, 32]      # sdf from primitives: samples along each axis
+//
+// [[mesh_colliders]] # any number of static triangle meshes
+// path = "meshes/torus.obj" # relative to the scene file
+// center = [0.0, 0.0, 0.0]
+// rotation = [0.0, 0.0, 0.0] # degrees around x, then y, then z
+// scale = 1.0
+// margin = 0.5      # distance kept between the cloth and the triangles
+// friction = 0.8    # optional, instead of physics.friction
+//
+// [ground]
+// enabled = false   # horizontal plane stopping the cloth, drawn as a grid
+// height = -10.0
+// friction = 0.5    # Coulomb friction coefficient of the ground, instead of physics.friction
+// margin = 0.2
+//
+// [camera]
+// eye = [20.0, 50.0, 50.0]
+// target = [0.0, 0.0, 0.0]
+// fovy = 75.0
+// znear = 0.1
+// zfar = 1000.0
+//
+// [light]
+// direction = [-0.4, -1.0, -0.3] # direction in which the light travels
+// color = [1.0, 1.0, 1.0]
+// ambient = 0.3
+// specular = 0.2                 # 0 disables the highlight
+// shininess = 32.0
+// two_sided = true
+//
+// [physics]
+// vertex_mass = 0.16
+// structural_stiffness = 150.0
+// ...
+// iterations = 500               # sub-steps per time step
+// time_step = 0.016666668       # seconds simulated per step, independent from the frame rate
+// max_steps_per_frame = 4       # the time above is dropped when the GPU cannot keep up
+// adaptive_iterations = false   # choose the sub-steps of every step from the stiffness, speed and strain
+// min_iterations = 1            # adaptive: range of the sub-steps per step
+// max_iterations = 1000
+// courant = 0.5                 # adaptive: fraction of the stable sub-step that is used
+// max_strain = 0.1              # adaptive: stretch above which the sub-steps are raised
+// friction = 0.3    # Coulomb friction coefficient of the collisions, 0 slides freely
+// restitution = 0.0 # fraction of the speed along the normal kept after a collision, 0 stops, 1 bounces
+// solver = "mass_spring"        # "xpbd" ignores the stiffness and damping of the springs, "implicit" is stable with a few sub-steps
+// integrator = "symplectic_euler" # mass_spring: or "verlet" or "rk4"
+// structural_compliance = 1e-4  # xpbd: inverse stiffness in m/N, 0 is inextensible
+// shear_compliance = 1e-3
+// bend_compliance = 1e-2        # xpbd: of the dihedral angle of the adjacent triangles, in rad/(N m)
+// damping = 0.1                 # xpbd: fraction of the velocity removed per second
+// cg_iterations = 50            # implicit: maximum conjugate gradient iterations per sub-step
+// cg_tolerance = 1e-3           # implicit: residual at which it stops, relative to the first one
+// =========================================================================================
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub cloth: ClothConfig,
+    pub sphere: SphereConfig,
+    pub colliders: Vec<ColliderConfig>,
+    pub mesh_colliders: Vec<MeshColliderConfig>,
+    pub ground: GroundConfig,
+    pub camera: CameraConfig,
+    pub light: LightConfig,
+    pub physics: PhysicsConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClothConfig {
+    pub width: f32,  // along x
+    pub height: f32, // along z
+    pub resolution: [u32; 2], // vertices along the width and the height, the minimum is 2
+    pub pins: Vec<[u32; 2]>, // grid coordinates of the vertices held in place
+    pub pinned_edges: Vec<ClothEdge>, // edges whose vertices are all held in place
+    pub center: [f32; 3],
+}
+
+// A side of the cloth grid, named after the axis it is perpendicular to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClothEdge {
+    XMin,
+    XMax,
+    ZMin,
+    ZMax,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SphereConfig {
+    pub enabled: bool,
+    pub radius: f32,
+    pub center: [f32; 3],
+    pub margin: f32, // the cloth collides with a sphere of radius + margin, the sphere is drawn with its radius
+    pub wireframe: bool,
+    pub velocity: [f32; 3], // the sphere moves from `center` at this velocity
+    pub keyframes: Vec<Keyframe>, // when not empty, the sphere follows them instead
+    pub looping: bool,
+}
+
+// Position of the sphere at a given time, in seconds from the start
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    pub time: f32,
+    pub center: [f32; 3],
+}
+
+// A static obstacle of the scene, the fields that its shape does not use are ignored
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColliderConfig {
+    pub shape: ColliderShape,
+    #[serde(default)]
+    pub center: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3], // degrees around x, then y, then z
+    #[serde(default)]
+    pub radius: f32, // sphere and capsule
+    #[serde(default)]
+    pub half_extents: [f32; 3], // box
+    #[serde(default)]
+    pub half_height: f32, // capsule, along its local y axis
+    #[serde(default = "default_margin")]
+    pub margin: f32,
+    #[serde(default)]
+    pub friction: Option<f32>, // physics.friction when not given
+    #[serde(default)]
+    pub path: Option<String>, // sdf: field baked offline
+    #[serde(default)]
+    pub primitives: Vec<ColliderConfig>, // sdf: union of shapes, in the local frame of the field
+    #[serde(default = "default_sdf_resolution")]
+    pub resolution: [u32; 3], // sdf from primitives
+    #[serde(skip)]
+    pub sdf: SdfGrid, // read from `path` or built from `primitives` when the scene is loaded
+}
+
+// A static triangle mesh loaded from an OBJ file, the front side of the triangles is the
+// side from which they are counter-clockwise
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshColliderConfig {
+    pub path: String,
+    #[serde(default)]
+    pub center: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3], // degrees around x, then y, then z
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default = "default_margin")]
+    pub margin: f32,
+    #[serde(default)]
+    pub friction: Option<f32>, // physics.friction when not given
+    #[serde(skip)]
+    pub mesh: ObjMesh, // read from `path` when the scene is loaded
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColliderShape {
+    Sphere,
+    Box,
+    Capsule,
+    Plane, // infinite, its normal is the local y axis
+    Sdf,   // signed distance field, see sdf.rs
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroundConfig {
+    pub enabled: bool,
+    pub height: f32,
+    pub friction: f32, // Coulomb coefficient, 0 slides freely
+    pub margin: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightConfig {
+    pub direction: [f32; 3], // direction in which the light travels
+    pub color: [f32; 3],
+    pub ambient: f32,
+    pub specular: f32, // strength of the Blinn-Phong highlight, 0 disables it
+    pub shininess: f32,
+    pub two_sided: bool, // light the back faces of the cloth like the front faces
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsConfig {
+    pub vertex_mass: f32,
+    pub structural_stiffness: f32,
+    pub shear_stiffness: f32,
+    pub bend_stiffness: f32,
+    pub structural_damping: f32,
+    pub shear_damping: f32,
+    pub bend_damping: f32,
+    pub iterations: u32, // number of sub-steps per time step
+    pub time_step: f32,  // seconds simulated per step, the frames run as many steps as their duration holds
+    pub max_steps_per_frame: u32, // the time above is dropped, see timestep.rs
+    pub adaptive_iterations: bool, // choose `iterations` at every step, see substeps.rs
+    pub min_iterations: u32, // adaptive: range of the sub-steps per step
+    pub max_iterations: u32,
+    pub courant: f32,    // adaptive: fraction of the longest stable sub-step that is used
+    pub max_strain: f32, // adaptive: relative stretch of the springs above which the sub-steps are raised
+    pub friction: f32,    // Coulomb friction coefficient of the collisions
+    pub restitution: f32, // fraction of the speed along the normal kept after a collision
+    pub solver: Solver,
+    pub integrator: Integrator, // mass_spring only
+    pub structural_compliance: f32, // xpbd: inverse stiffness of the constraints, in m/N
+    pub shear_compliance: f32,
+    pub bend_compliance: f32, // xpbd: of the dihedral angle constraints, in rad/(N m)
+    pub damping: f32, // xpbd: fraction of the velocity removed per second
+    pub cg_iterations: u32, // implicit: maximum conjugate gradient iterations per step
+    pub cg_tolerance: f32,  // implicit: residual at which the conjugate gradient stops, relative to the first one
+}
+
+// How the springs move the cloth at every sub-step
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Solver {
+    #[default]
+    MassSpring, // spring forces integrated with semi-implicit Euler
+    Xpbd,       // distance and bending constraints projected on the positions, see xpbd.rs
+    Implicit,   // backward Euler solved with a conjugate gradient, see implicit.rs
+}
+
+// How the mass-spring solver advances the positions and velocities from the spring forces
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    #[default]
+    SymplecticEuler, // v = v + a dt, x = x + v dt (compute.wgsl)
+    Verlet,          // position Verlet, with the displacement of the previous step (integrators.wgsl)
+    Rk4,             // fourth order Runge-Kutta, 4 force evaluations per step (integrators.wgsl)
+}
+
+impl Default for ClothConfig {
+    fn default() -> Self {
+        Self {
+            width: 50.0,
+            height: 50.0,
+            resolution: [25, 25],
+            pins: Vec::new(),
+            pinned_edges: Vec::new(),
+            center: [0.0, 10.5, 0.0], // just touching the sphere and its margin
+        }
+    }
+}
+
+impl Default for SphereConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            radius: 10.0,
+            center: [0.0, 0.0, 0.0],
+            margin: default_margin(),
+            wireframe: false,
+            velocity: [0.0, 0.0, 0.0],
+            keyframes: Vec::new(),
+            looping: false,
+        }
+    }
+}
+
+impl Default for GroundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: -10.0, // under the sphere
+            friction: 0.5,
+            margin: 0.2,
+        }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            eye: [20.0, 50.0, 50.0],
+            target: [0.0, 0.0, 0.0],
+            fovy: 75.0,
+            znear: 0.1,
+            zfar: 1000.0,
+        }
+    }
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        Self {
+            direction: [-0.4, -1.0, -0.3],
+            color: [1.0, 1.0, 1.0],
+            ambient: 0.3,
+            specular: 0.2,
+            shininess: 32.0,
+            two_sided: true,
+        }
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            vertex_mass: 0.16,
+            structural_stiffness: 150.0,
+            shear_stiffness: 5.0,
+            bend_stiffness: 15.0,
+            structural_damping: 1.5,
+            shear_damping: 0.05,
+            bend_damping: 0.15,
+            iterations: 500,
+            time_step: 1.0 / 60.0,
+            max_steps_per_frame: 4,
+            adaptive_iterations: false,
+            min_iterations: 1,
+            max_iterations: 1000,
+            courant: 0.5,
+            max_strain: 0.1,
+            friction: 0.3,
+            restitution: 0.0,
+            solver: Solver::MassSpring,
+            integrator: Integrator::SymplecticEuler,
+            structural_compliance: 1e-4,
+            shear_compliance: 1e-3,
+            bend_compliance: 1e-2,
+            damping: 0.1,
+            cg_iterations: 50,
+            cg_tolerance: 1e-3,
+        }
+    }
+}
+
+// Everything that can go wrong while loading a scene file
+#[derive(Debug)]
+pub enum SceneError {
+    Io(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    Mesh(String, ObjError),
+    Sdf(String, SdfError),
+    Invalid { field: &'static str, reason: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(path, err) => write!(f, "cannot read scene file '{}': {}", path, err),
+            SceneError::Parse(path, err) => write!(f, "cannot parse scene file '{}': {}", path, err),
+            SceneError::Mesh(path, err) => write!(f, "cannot load mesh collider '{}': {}", path, err),
+            SceneError::Sdf(path, err) => write!(f, "cannot load signed distance field '{}': {}", path, err),
+            SceneError::Invalid { field, reason } => write!(f, "invalid scene field '{}': {}", field, reason),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    // Read, parse and validate a scene file, the mesh files are relative to it
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| SceneError::Io(path.display().to_string(), err))?;
+        Self::parse_in(&text, path.parent().unwrap_or(Path::new(""))).map_err(|err| match err {
+            SceneError::Parse(_, err) => SceneError::Parse(path.display().to_string(), err),
+            err => err,
+        })
+    }
+
+    // Parse and validate a scene from a TOML string, the mesh files are relative to the
+    // current directory
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        Self::parse_in(text, Path::new(""))
+    }
+
+    fn parse_in(text: &str, directory: &Path) -> Result<Self, SceneError> {
+        let mut scene: Scene = toml::from_str(text)
+            .map_err(|err| SceneError::Parse(String::from("<string>"), err))?;
+        scene.validate()?;
+        for mesh_collider in &mut scene.mesh_colliders {
+            let path = directory.join(&mesh_collider.path);
+            mesh_collider.mesh = ObjMesh::load(&path)
+                .map_err(|err| SceneError::Mesh(path.display().to_string(), err))?;
+        }
+
+        // the signed distance fields, all stacked along z in one 3D texture
+        let mut atlas_depth = 0;
+        for collider in scene.colliders.iter_mut().filter(|collider| collider.shape == ColliderShape::Sdf) {
+            collider.sdf = match &collider.path {
+                Some(path) => {
+                    let path = directory.join(path);
+                    SdfGrid::load(&path).map_err(|err| SceneError::Sdf(path.display().to_string(), err))?
+                }
+                None => {
+                    let primitives: Vec<Collider> = collider.primitives.iter().map(Collider::new).collect();
+                    SdfGrid::from_primitives(&primitives, collider.half_extents, collider.resolution)
+                }
+            };
+            atlas_depth += collider.sdf.resolution[2];
+        }
+        if atlas_depth > MAX_ATLAS_DEPTH {
+            return Err(invalid("colliders.resolution", format!("the signed distance fields have {} samples along z in total, the maximum is {}", atlas_depth, MAX_ATLAS_DEPTH)));
+        }
+        Ok(scene)
+    }
+
+    // Check the values that would otherwise make the simulation panic or explode
+    pub fn validate(&self) -> Result<(), SceneError> {
+        positive("cloth.width", self.cloth.width)?;
+        positive("cloth.height", self.cloth.height)?;
+        let [nx, nz] = self.cloth.resolution;
+        if nx < 2 || nz < 2 {
+            return Err(invalid("cloth.resolution", format!("must be at least 2 in both directions, got {:?}", self.cloth.resolution)));
+        }
+        if nx.checked_mul(nz).is_none() {
+            return Err(invalid("cloth.resolution", format!("{} x {} vertices do not fit in 32-bit indices", nx, nz)));
+        }
+        if let Some(pin) = self.cloth.pins.iter().find(|pin| pin[0] >= nx || pin[1] >= nz) {
+            return Err(invalid("cloth.pins", format!("{:?} is outside of the {} x {} grid", pin, nx, nz)));
+        }
+        finite3("cloth.center", self.cloth.center)?;
+
+        positive("sphere.radius", self.sphere.radius)?;
+        finite3("sphere.center", self.sphere.center)?;
+        non_negative("sphere.margin", self.sphere.margin)?;
+        finite3("sphere.velocity", self.sphere.velocity)?;
+        if !self.sphere.keyframes.is_empty() && self.sphere.velocity != [0.0, 0.0, 0.0] {
+            return Err(invalid("sphere.velocity", String::from("cannot be used with sphere.keyframes")));
+        }
+        for (i, keyframe) in self.sphere.keyframes.iter().enumerate() {
+            finite3("sphere.keyframes", keyframe.center)?;
+            non_negative("sphere.keyframes", keyframe.time)?;
+            if i > 0 && keyframe.time <= self.sphere.keyframes[i - 1].time {
+                return Err(invalid("sphere.keyframes", format!("the times must be increasing, got {} after {}", keyframe.time, self.sphere.keyframes[i - 1].time)));
+            }
+        }
+
+        for collider in &self.colliders {
+            validate_collider(collider)?;
+        }
+
+        for mesh_collider in &self.mesh_colliders {
+            finite3("mesh_colliders.center", mesh_collider.center)?;
+            finite3("mesh_colliders.rotation", mesh_collider.rotation)?;
+            positive("mesh_colliders.scale", mesh_collider.scale)?;
+            positive("mesh_colliders.margin", mesh_collider.margin)?; // the triangles have no inside
+            if let Some(friction) = mesh_collider.friction {
+                non_negative("mesh_colliders.friction", friction)?;
+            }
+        }
+
+        if !self.ground.height.is_finite() {
+            return Err(invalid("ground.height", format!("must be a finite number, got {}", self.ground.height)));
+        }
+        non_negative("ground.friction", self.ground.friction)?;
+        non_negative("ground.margin", self.ground.margin)?;
+
+        finite3("camera.eye", self.camera.eye)?;
+        finite3("camera.target", self.camera.target)?;
+        if self.camera.eye == self.camera.target {
+            return Err(invalid("camera.eye", String::from("must be different from camera.target")));
+        }
+        if !self.camera.fovy.is_finite() || self.camera.fovy <= 0.0 || self.camera.fovy >= 180.0 {
+            return Err(invalid("camera.fovy", format!("must be between 0 and 180 degrees, got {}", self.camera.fovy)));
+        }
+        positive("camera.znear", self.camera.znear)?;
+        if !self.camera.zfar.is_finite() || self.camera.zfar <= self.camera.znear {
+            return Err(invalid("camera.zfar", format!("must be greater than camera.znear, got {}", self.camera.zfar)));
+        }
+
+        finite3("light.direction", self.light.direction)?;
+        if self.light.direction == [0.0, 0.0, 0.0] {
+            return Err(invalid("light.direction", String::from("must not be zero")));
+        }
+        finite3("light.color", self.light.color)?;
+        non_negative("light.ambient", self.light.ambient)?;
+        non_negative("light.specular", self.light.specular)?;
+        positive("light.shininess", self.light.shininess)?;
+
+        positive("physics.vertex_mass", self.physics.vertex_mass)?;
+        non_negative("physics.structural_stiffness", self.physics.structural_stiffness)?;
+        non_negative("physics.shear_stiffness", self.physics.shear_stiffness)?;
+        non_negative("physics.bend_stiffness", self.physics.bend_stiffness)?;
+        non_negative("physics.structural_damping", self.physics.structural_damping)?;
+        non_negative("physics.shear_damping", self.physics.shear_damping)?;
+        non_negative("physics.bend_damping", self.physics.bend_damping)?;
+        if self.physics.iterations == 0 {
+            return Err(invalid("physics.iterations", String::from("must be at least 1")));
+        }
+        positive("physics.time_step", self.physics.time_step)?;
+        if self.physics.max_steps_per_frame == 0 {
+            return Err(invalid("physics.max_steps_per_frame", String::from("must be at least 1")));
+        }
+        if self.physics.min_iterations == 0 {
+            return Err(invalid("physics.min_iterations", String::from("must be at least 1")));
+        }
+        if self.physics.max_iterations < self.physics.min_iterations {
+            return Err(invalid("physics.max_iterations", format!("must be at least physics.min_iterations ({})", self.physics.min_iterations)));
+        }
+        positive("physics.courant", self.physics.courant)?;
+        positive("physics.max_strain", self.physics.max_strain)?;
+        non_negative("physics.friction", self.physics.friction)?;
+        if !(0.0..=1.0).contains(&self.physics.restitution) {
+            return Err(invalid("physics.restitution", format!("must be between 0 and 1, got {}", self.physics.restitution)));
+        }
+        if self.physics.solver != Solver::MassSpring && self.physics.integrator != Integrator::SymplecticEuler {
+            return Err(invalid("physics.integrator", String::from("is only used by the mass_spring solver")));
+        }
+        non_negative("physics.structural_compliance", self.physics.structural_compliance)?;
+        non_negative("physics.shear_compliance", self.physics.shear_compliance)?;
+        non_negative("physics.bend_compliance", self.physics.bend_compliance)?;
+        non_negative("physics.damping", self.physics.damping)?;
+        if self.physics.cg_iterations == 0 {
+            return Err(invalid("physics.cg_iterations", String::from("must be at least 1")));
+        }
+        non_negative("physics.cg_tolerance", self.physics.cg_tolerance)?;
+        Ok(())
+    }
+
+    // Total number of vertices of the cloth
+    pub fn nb_cloth_vertices(&self) -> u32 {
+        self.cloth.resolution[0] * self.cloth.resolution[1]
+    }
+}
+
+impl ClothConfig {
+    // Distance between two neighbor vertices along x and along z, the cells are not square
+    // when the width and the height are not divided by the same number of cells
+    pub fn cell_size(&self) -> [f32; 2] {
+        [
+            self.width / (self.resolution[0] - 1) as f32,
+            self.height / (self.resolution[1] - 1) as f32,
+        ]
+    }
+}
+
+fn default_margin() -> f32 {
+    0.5
+}
+
+fn default_sdf_resolution() -> [u32; 3] {
+    [32, 32, 32]
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn invalid(field: &'static str, reason: String) -> SceneError {
+    SceneError::Invalid { field, reason }
+}
+
+fn validate_collider(collider: &ColliderConfig) -> Result<(), SceneError> {
+    finite3("colliders.center", collider.center)?;
+    finite3("colliders.rotation", collider.rotation)?;
+    non_negative("colliders.margin", collider.margin)?;
+    if let Some(friction) = collider.friction {
+        non_negative("colliders.friction", friction)?;
+    }
+    match collider.shape {
+        ColliderShape::Sphere => positive("colliders.radius", collider.radius)?,
+        ColliderShape::Capsule => {
+            positive("colliders.radius", collider.radius)?;
+            non_negative("colliders.half_height", collider.half_height)?;
+        }
+        ColliderShape::Box => {
+            for half_extent in collider.half_extents {
+                positive("colliders.half_extents", half_extent)?;
+            }
+        }
+        ColliderShape::Plane => {}
+        ColliderShape::Sdf => match (&collider.path, collider.primitives.is_empty()) {
+            (Some(_), false) => return Err(invalid("colliders.path", String::from("cannot be used with colliders.primitives"))),
+            (None, true) => return Err(invalid("colliders.path", String::from("an sdf collider needs a path or primitives"))),
+            (Some(_), true) => {}
+            (None, false) => {
+                for half_extent in collider.half_extents {
+                    positive("colliders.half_extents", half_extent)?;
+                }
+                if collider.resolution.iter().any(|n| !(2..=MAX_SDF_RESOLUTION).contains(n)) {
+                    return Err(invalid("colliders.resolution", format!("must be between 2 and {}, got {:?}", MAX_SDF_RESOLUTION, collider.resolution)));
+                }
+                for primitive in &collider.primitives {
+                    if primitive.shape == ColliderShape::Sdf {
+                        return Err(invalid("colliders.primitives", String::from("cannot contain an sdf")));
+                    }
+                    validate_collider(primitive)?;
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+fn positive(field: &'static str, value: f32) -> Result<(), SceneError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(field, format!("must be a positive number, got {}", value)))
+    }
+}
+
+fn non_negative(field: &'static str, value: f32) -> Result<(), SceneError> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(field, format!("must be zero or a positive number, got {}", value)))
+    }
+}
+
+fn finite3(field: &'static str, value: [f32; 3]) -> Result<(), SceneError> {
+    if value.iter().all(|v| v.is_finite()) {
+        Ok(())
+    } else {
+        Err(invalid(field, format!("must only contain finite numbers, got {:?}", value)))
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::path::Path;
+
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    default::Vertex,
+};
+
+use crate::colliders::Collider;
+use crate::mesh::{closest_point_on_triangle, MeshTriangle, ObjMesh};
+
+// =========================================================================================
+//                                 SIGNED DISTANCE FIELDS
+// =========================================================================================
+// A signed distance field is a grid of distances to a surface, negative inside, sampled in
+// the local frame of a collider over the box [-half_extents, half_extents]. compute.wgsl
+// reads it from a 3D texture with trilinear interpolation: the distance is the penetration
+// depth and its gradient is the normal, so any shape costs the same 8 + 48 texel reads.
+//
+// A field is either baked offline from a closed OBJ mesh with `--bake-sdf` and saved in a
+// small binary file, or built when the scene is loaded from a union of primitives.
+// All the fields of a scene are stacked along z in a single texture, the atlas.
+//
+// File format, little endian: "SDF1", resolution (3 x u32), half extents (3 x f32), then
+// the distances (f32), x first, then y, then z.
+// =========================================================================================
+
+const MAGIC: &[u8; 4] = b"SDF1";
+
+// Largest resolution along each axis, and largest depth of the atlas (the default
+// max_texture_dimension_3d of wgpu)
+pub const MAX_SDF_RESOLUTION: u32 = 256;
+pub const MAX_ATLAS_DEPTH: u32 = 2048;
+
+// Everything that can go wrong while reading or writing a signed distance field file
+#[derive(Debug)]
+pub enum SdfError {
+    Io(std::io::Error),
+    Format(String),
+}
+
+impl fmt::Display for SdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdfError::Io(err) => write!(f, "{}", err),
+            SdfError::Format(reason) => write!(f, "not a signed distance field file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SdfError {}
+
+// The distances sampled at -half_extents + i * cell_size along each axis
+#[derive(Clone, Debug, Default)]
+pub struct SdfGrid {
+    pub resolution: [u32; 3],
+    pub half_extents: [f32; 3],
+    pub values: Vec<f32>,
+}
+
+impl SdfGrid {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SdfError> {
+        let bytes = std::fs::read(path).map_err(SdfError::Io)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SdfError> {
+        std::fs::write(path, self.to_bytes()).map_err(SdfError::Io)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SdfError> {
+        if bytes.len() < 28 || &bytes[0..4] != MAGIC {
+            return Err(SdfError::Format(String::from("missing the SDF1 header")));
+        }
+        let word = |i: usize| [bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]];
+        let resolution = [1, 2, 3].map(|i| u32::from_le_bytes(word(i)));
+        if resolution.iter().any(|n| !(2..=MAX_SDF_RESOLUTION).contains(n)) {
+            return Err(SdfError::Format(format!("the resolution must be between 2 and {}, got {:?}", MAX_SDF_RESOLUTION, resolution)));
+        }
+        let half_extents = [4, 5, 6].map(|i| f32::from_le_bytes(word(i)));
+        if half_extents.iter().any(|&h| !(h.is_finite() && h > 0.0)) {
+            return Err(SdfError::Format(format!("the half extents must be positive, got {:?}", half_extents)));
+        }
+        let nb_values = (resolution[0] * resolution[1] * resolution[2]) as usize;
+        if bytes.len() != (7 + nb_values) * 4 {
+            return Err(SdfError::Format(format!("expected {} distances for a resolution of {:?}", nb_values, resolution)));
+        }
+        Ok(Self {
+            resolution,
+            half_extents,
+            values: (0..nb_values).map(|i| f32::from_le_bytes(word(7 + i))).collect(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for n in self.resolution {
+            bytes.extend(n.to_le_bytes());
+        }
+        for h in self.half_extents.iter().chain(&self.values) {
+            bytes.extend(h.to_le_bytes());
+        }
+        bytes
+    }
+
+    // Distance to the union of primitives, in the local frame of the field
+    pub fn from_primitives(primitives: &[Collider], half_extents: [f32; 3], resolution: [u32; 3]) -> Self {
+        Self::sample(half_extents, resolution, |point| {
+            primitives
+                .iter()
+                .map(|primitive| primitive.signed_distance(point))
+                .fold(f32::MAX, f32::min)
+        })
+    }
+
+    // Distance to a closed mesh, `resolution` samples along its largest side. The box is
+    // centered on the origin of the mesh and a little larger than the mesh, so that the
+    // margin of the collider fits in it.
+    pub fn bake_mesh(mesh: &ObjMesh, resolution: u32) -> Self {
+        let triangles: Vec<MeshTriangle> = mesh
+            .triangles
+            .iter()
+            .map(|&[a, b, c]| MeshTriangle {
+                a: mesh.positions[a as usize],
+                b: mesh.positions[b as usize],
+                c: mesh.positions[c as usize],
+                margin: 0.0,
+                friction: 0.0,
+            })
+            .collect();
+
+        let mut half_extents = [0.0f32; 3];
+        for position in &mesh.positions {
+            for axis in 0..3 {
+                half_extents[axis] = half_extents[axis].max(position[axis].abs());
+            }
+        }
+        let largest = half_extents.iter().cloned().fold(0.0, f32::max).max(f32::EPSILON);
+        let padding = 0.1 * largest;
+        let half_extents = half_extents.map(|h| h + padding);
+        let cell_size = 2.0 * (largest + padding) / (resolution - 1) as f32;
+        let resolution = half_extents.map(|h| ((2.0 * h / cell_size).ceil() as u32 + 1).clamp(2, resolution));
+
+        Self::sample(half_extents, resolution, |point| {
+            let distance = triangles
+                .iter()
+                .map(|triangle| (point - closest_point_on_triangle(point, triangle)).magnitude())
+                .fold(f32::MAX, f32::min);
+            if winding_number(point, &triangles) > 0.5 {
+                -distance
+            } else {
+                distance
+            }
+        })
+    }
+
+    fn sample(half_extents: [f32; 3], resolution: [u32; 3], distance: impl Fn(Vector3<f32>) -> f32) -> Self {
+        let [nx, ny, nz] = resolution;
+        let position = |i: u32, axis: usize| -half_extents[axis] + 2.0 * half_extents[axis] * i as f32 / (resolution[axis] - 1) as f32;
+        let mut values = Vec::with_capacity((nx * ny * nz) as usize);
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    values.push(distance(Vector3::new(position(x, 0), position(y, 1), position(z, 2))));
+                }
+            }
+        }
+        Self {
+            resolution,
+            half_extents,
+            values,
+        }
+    }
+}
+
+// Sum of the solid angles of the triangles seen from a point, divided by 4 pi: 1 inside a
+// closed mesh and 0 outside, even when the mesh has small holes
+fn winding_number(point: Vector3<f32>, triangles: &[MeshTriangle]) -> f32 {
+    let mut solid_angle = 0.0;
+    for triangle in triangles {
+        // Van Oosterom and Strackee
+        let a = Vector3::from(triangle.a) - point;
+        let b = Vector3::from(triangle.b) - point;
+        let c = Vector3::from(triangle.c) - point;
+        let (la, lb, lc) = (a.magnitude(), b.magnitude(), c.magnitude());
+        let numerator = a.dot(b.cross(c));
+        let denominator = la * lb * lc + a.dot(b) * lc + b.dot(c) * la + c.dot(a) * lb;
+        solid_angle += 2.0 * numerator.atan2(denominator);
+    }
+    solid_angle / (4.0 * std::f32::consts::PI)
+}
+
+// Every field of a scene stacked along z, uploaded into the 3D texture of compute.wgsl
+pub struct SdfAtlas {
+    pub size: [u32; 3],
+    pub values: Vec<f32>,
+}
+
+impl SdfAtlas {
+    // The fields in order, the z offset of each one is the sum of the depths before it.
+    // A single texel when there is none, a texture cannot be empty.
+    pub fn new(grids: &[&SdfGrid]) -> Self {
+        if grids.is_empty() {
+            return Self {
+                size: [1, 1, 1],
+                values: vec![0.0],
+            };
+        }
+        let width = grids.iter().map(|grid| grid.resolution[0]).max().unwrap_or(1);
+        let height = grids.iter().map(|grid| grid.resolution[1]).max().unwrap_or(1);
+        let depth = grids.iter().map(|grid| grid.resolution[2]).sum();
+        let mut values = vec![0.0; (width * height * depth) as usize];
+        let mut z_offset = 0;
+        for grid in grids {
+            let [nx, ny, nz] = grid.resolution;
+            for z in 0..nz {
+                for y in 0..ny {
+                    let source = ((z * ny + y) * nx) as usize;
+                    let destination = (((z + z_offset) * height + y) * width) as usize;
+                    values[destination..destination + nx as usize].copy_from_slice(&grid.values[source..source + nx as usize]);
+                }
+            }
+            z_offset += nz;
+        }
+        Self {
+            size: [width, height, depth],
+            values,
+        }
+    }
+
+    // Same as `textureLoad` in compute.wgsl
+    pub fn load(&self, x: u32, y: u32, z: u32) -> f32 {
+        let [width, height, _] = self.size;
+        self.values[((z * height + y) * width + x) as usize]
+    }
+}
+
+// The zero level of the field of a collider in its local frame, with surface nets: one
+// vertex per cell crossed by the surface, at the average of the crossings of its edges,
+// and one quad around every crossed edge of the grid. The margin is not drawn.
+pub fn sdf_mesh(collider: &Collider, atlas: &SdfAtlas) -> (Vec<Vertex>, Vec<u32>) {
+    let [nx, ny, nz] = collider.sdf_resolution;
+    let h = Vector3::from(collider.half_extents);
+    let cell = Vector3::new(2.0 * h.x / (nx - 1) as f32, 2.0 * h.y / (ny - 1) as f32, 2.0 * h.z / (nz - 1) as f32);
+    let value = |x: u32, y: u32, z: u32| atlas.load(x, y, z + collider.sdf_offset);
+    let point = |x: u32, y: u32, z: u32| Vector3::new(-h.x + x as f32 * cell.x, -h.y + y as f32 * cell.y, -h.z + z as f32 * cell.z);
+    let cell_index = |x: u32, y: u32, z: u32| ((z * (ny - 1) + y) * (nx - 1) + x) as usize;
+
+    // one vertex per crossed cell
+    let mut cell_vertices = vec![u32::MAX; ((nx - 1) * (ny - 1) * (nz - 1)) as usize];
+    let mut vertices = Vec::new();
+    for z in 0..nz - 1 {
+        for y in 0..ny - 1 {
+            for x in 0..nx - 1 {
+                let mut sum = Vector3::new(0.0, 0.0, 0.0);
+                let mut crossings = 0;
+                for (a, b) in CELL_EDGES {
+                    let (pa, pb) = (corner(x, y, z, a), corner(x, y, z, b));
+                    let (va, vb) = (value(pa[0], pa[1], pa[2]), value(pb[0], pb[1], pb[2]));
+                    if (va < 0.0) != (vb < 0.0) {
+                        let t = va / (va - vb);
+                        sum += point(pa[0], pa[1], pa[2]) * (1.0 - t) + point(pb[0], pb[1], pb[2]) * t;
+                        crossings += 1;
+                    }
+                }
+                if crossings > 0 {
+                    let position = sum / crossings as f32;
+                    let gradient = collider.sdf_gradient(position, atlas);
+                    let normal = if gradient.magnitude2() > 0.0 { gradient.normalize() } else { Vector3::unit_y() };
+                    cell_vertices[cell_index(x, y, z)] = vertices.len() as u32;
+                    vertices.push(Vertex {
+                        position: position.into(),
+                        normal: normal.into(),
+                        tangent: [0.0, 0.0, 0.0],
+                        tex_coords: [0.0, 0.0],
+                    });
+                }
+            }
+        }
+    }
+
+    // one quad around every crossed edge that is not on the border of the grid
+    let mut indices = Vec::new();
+    for z in 0..nz {
+        for y in 0..ny {
+            for x in 0..nx {
+                let p = [x, y, z];
+                for axis in 0..3 {
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    if p[axis] + 1 >= collider.sdf_resolution[axis] || p[u] == 0 || p[v] == 0 {
+                        continue;
+                    }
+                    if p[u] >= collider.sdf_resolution[u] - 1 || p[v] >= collider.sdf_resolution[v] - 1 {
+                        continue;
+                    }
+                    let mut q = p;
+                    q[axis] += 1;
+                    if (value(x, y, z) < 0.0) == (value(q[0], q[1], q[2]) < 0.0) {
+                        continue;
+                    }
+                    // the 4 cells sharing the edge
+                    let cells = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(du, dv)| {
+                        let mut c = p;
+                        c[u] -= du;
+                        c[v] -= dv;
+                        cell_vertices[cell_index(c[0], c[1], c[2])]
+                    });
+                    if cells.contains(&u32::MAX) {
+                        continue;
+                    }
+                    for triangle in [[cells[0], cells[1], cells[2]], [cells[0], cells[2], cells[3]]] {
+                        indices.extend(oriented(triangle, &vertices));
+                    }
+                }
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+// The 12 edges of a cell, as pairs of corners numbered by their x, y and z bits
+const CELL_EDGES: [(u32, u32); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+fn corner(x: u32, y: u32, z: u32, corner: u32) -> [u32; 3] {
+    [x + (corner & 1), y + ((corner >> 1) & 1), z + ((corner >> 2) & 1)]
+}
+
+// Counter-clockwise seen from outside, where the gradient of the field points
+fn oriented(triangle: [u32; 3], vertices: &[Vertex]) -> [u32; 3] {
+    let [a, b, c] = triangle.map(|i| Vector3::from(vertices[i as usize].position));
+    let outside: Vector3<f32> = triangle.iter().map(|&i| Vector3::from(vertices[i as usize].normal)).sum();
+    if (b - a).cross(c - a).dot(outside) >= 0.0 {
+        triangle
+    } else {
+        [triangle[0], triangle[2], triangle[1]]
+    }
+}
@@ -0,0 +1,1204 @@
+use wgpu_bootstrap::{
+    wgpu::{self, util::DeviceExt},
+    default::Vertex,
+};
+
+use crate::colliders::{Collider, Colliders};
+use crate::sdf::SdfAtlas;
+use crate::cloth::{ComputeData, Indices, Velocity, create_cloth_vertices, create_cloth_indices, create_forces, create_inverse_masses, create_velocities};
+use crate::layout::{COMPUTE_SHADER, FORCES_SHADER, IMPLICIT_SHADER, INTEGRATORS_SHADER, INTERPOLATE_SHADER, MESH_GRID_SHADER, NORMALS_SHADER, STATS_SHADER, XPBD_SHADER};
+use crate::mesh::MeshColliders;
+use crate::normals::{compute_normals, TriangleAdjacency};
+use crate::scene::{Integrator, Scene, Solver};
+use crate::springs::SpringTopology;
+use crate::substeps::{MotionStats, SubstepController};
+use crate::timestep::InterpolationData;
+use crate::xpbd::XpbdConstraints;
+
+// =========================================================================================
+//                                   CLOTH SIMULATION
+// =========================================================================================
+
+// The cloth buffers and the force/integration pipelines, built from forces.wgsl and compute.wgsl
+// (with the conjugate gradient of implicit.wgsl in between, the Verlet or RK4 passes of
+// integrators.wgsl instead of compute.wgsl, or the XPBD pipelines of xpbd.wgsl instead of both),
+// and the pipeline of normals.wgsl that updates the normals and tangents for the rendering.
+// The grid of the mesh colliders is built by mesh_grid.wgsl and the signed distance fields
+// are uploaded before the first iteration. With physics.adaptive_iterations, stats.wgsl
+// measures the cloth before every step to choose its number of sub-steps (see substeps.rs).
+// It only needs a wgpu device and queue, so it can be embedded in any wgpu application:
+// call `step` for every fixed time step of the frame (see timestep.rs), then `interpolate`,
+// and draw `render_vertex_buffer` with `index_buffer`.
+pub struct ClothSimulation {
+    forces_compute_pipeline: wgpu::ComputePipeline,
+    compute_pipeline: wgpu::ComputePipeline,
+    normals_compute_pipeline: wgpu::ComputePipeline,
+    interpolation: Interpolation,
+    adaptive: Option<AdaptiveSubsteps>, // chooses n_iterations before every step when physics.adaptive_iterations is set
+    xpbd: Option<XpbdPipelines>, // replaces the forces and integration passes when physics.solver is xpbd
+    implicit: Option<ImplicitPipelines>, // runs between the forces and integration passes when physics.solver is implicit
+    integrator: Option<IntegratorPipelines>, // replaces the integration pass when physics.integrator is not symplectic_euler
+    data_bind_group: wgpu::BindGroup, // the compute data alone, group 2 of implicit.wgsl and integrators.wgsl
+    bind_groups: [wgpu::BindGroup; 4],
+    triangles_bind_group: wgpu::BindGroup,
+    mesh_bind_group: wgpu::BindGroup,
+    mesh_grid_pipelines: [wgpu::ComputePipeline; 3], // count, scan and fill
+    mesh_grid_bind_group: wgpu::BindGroup,
+    cell_counts_buffer: wgpu::Buffer,
+    nb_mesh_triangles: u32,
+    sdf_atlas_texture: wgpu::Texture,
+    initialized: bool, // the mesh grid is built and the signed distance fields uploaded
+    cloth_vertex_buffer: wgpu::Buffer,
+    cloth_index_buffer: wgpu::Buffer,
+    cloth_velocities_buffer: wgpu::Buffer,
+    inverse_masses_buffer: wgpu::Buffer,
+    inverse_masses: Vec<f32>, // CPU copy, rewritten to the buffer when a vertex is pinned or released
+    released_inverse_masses: Vec<f32>, // given to `with_mesh`, restored when a vertex is released
+    compute_data_buffer: wgpu::Buffer,
+    compute_data: ComputeData,
+    colliders_buffer: wgpu::Buffer,
+    colliders: Colliders,
+    nb_vertices: u32,
+    nb_indices: u32,
+    index_format: wgpu::IndexFormat,
+    n_iterations: u32, // sub-steps per step, chosen again at every step by `adaptive`
+}
+
+// The pass of interpolate.wgsl, with the cloth before the last step and the cloth it draws
+struct Interpolation {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup, // data, current, previous and render vertices
+    data_buffer: wgpu::Buffer,
+    previous_vertex_buffer: wgpu::Buffer, // copied from the cloth vertices before every step
+    render_vertex_buffer: wgpu::Buffer,
+}
+
+// The pass of stats.wgsl measuring the cloth before every step, and what it decides
+struct AdaptiveSubsteps {
+    controller: SubstepController,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup, // vertices, velocities, inverse masses, springs and the stats
+    stats_buffer: wgpu::Buffer,  // the bits of the largest speed and strain
+    staging_buffer: wgpu::Buffer, // the stats copied to be mapped
+    pending: Option<PendingStats>, // the stats of the last step, mapped while it runs
+    error: Option<wgpu::BufferAsyncError>, // why the last read back failed, None once one succeeds
+}
+
+// The submission measuring the stats and the result of the mapping of the staging buffer
+type PendingStats = (wgpu::SubmissionIndex, std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>);
+
+// The three passes of an XPBD sub-step, with the constraints of every color
+struct XpbdPipelines {
+    predict_pipeline: wgpu::ComputePipeline,
+    project_pipeline: wgpu::ComputePipeline,
+    finalize_pipeline: wgpu::ComputePipeline,
+    color_bind_groups: Vec<(wgpu::BindGroup, u32)>, // one per color, with its number of constraints
+}
+
+// The passes of the conjugate gradient of implicit.wgsl: init, reduce_start, then every
+// iteration product, reduce_alpha, update, reduce_beta and direction, and finally apply
+struct ImplicitPipelines {
+    pipelines: [wgpu::ComputePipeline; 8],
+    bind_group: wgpu::BindGroup, // group 3: the springs and the solver buffers
+    cg_iterations: u32,
+}
+
+// The passes of integrators.wgsl: verlet_main alone, or rk4_begin_main and the 4 stages that
+// follow a forces pass. The collisions are handled by collide_main of compute.wgsl.
+struct IntegratorPipelines {
+    pipelines: Vec<wgpu::ComputePipeline>,
+    collide_pipeline: wgpu::ComputePipeline,
+    state_bind_group: wgpu::BindGroup, // group 3: the Verlet displacements or the RK4 stages
+}
+
+impl ClothSimulation {
+    // The rectangular grid cloth described by the scene
+    pub fn new(device: &wgpu::Device, scene: &Scene) -> Self {
+        Self::with_mesh(
+            device,
+            scene,
+            &create_cloth_vertices(scene),
+            &create_cloth_indices(scene),
+            &SpringTopology::grid(scene),
+            &create_inverse_masses(scene),
+        )
+    }
+
+    // Any cloth mesh with its springs (built with SpringTopology::from_edges) and the inverse
+    // mass of every vertex (0 to pin it), the cloth parameters of the scene are not used
+    pub fn with_mesh(
+        device: &wgpu::Device,
+        scene: &Scene,
+        vertices: &[Vertex],
+        indices: &Indices,
+        springs: &SpringTopology,
+        inverse_masses: &[f32],
+    ) -> Self {
+        let nb_vertices = vertices.len() as u32;
+        assert_eq!(inverse_masses.len(), vertices.len(), "one inverse mass is needed per vertex");
+        let colliders = Colliders::new(scene);
+        let mut compute_data = ComputeData::new(scene, colliders.len() as u32);
+        compute_data.nb_vertices = nb_vertices;
+        let mesh_colliders = MeshColliders::new(scene);
+
+        // The normals of the initial mesh, they are updated by normals.wgsl after every step
+        let adjacency = TriangleAdjacency::new(nb_vertices, &indices.to_u32());
+        let mut vertices = vertices.to_vec();
+        compute_normals(&mut vertices, &adjacency);
+
+        // =====================================================================
+        //                              Buffers
+        // =====================================================================
+        let cloth_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let cloth_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Index Buffer"),
+            contents: indices.as_bytes(),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let cloth_velocities_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Velocities Buffer"),
+            contents: bytemuck::cast_slice(&create_velocities(nb_vertices)),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let cloth_forces_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Forces Buffer"),
+            contents: bytemuck::cast_slice(&create_forces(nb_vertices)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let inverse_masses_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Inverse Masses Buffer"),
+            contents: bytemuck::cast_slice(inverse_masses),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let compute_data_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Compute Data Buffer"),
+            contents: bytemuck::cast_slice(&[compute_data]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let colliders_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Colliders Buffer"),
+            contents: bytemuck::cast_slice(&colliders.buffer_contents()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        // every signed distance field in one 3D texture, read with textureLoad so the 32-bit
+        // floats do not need to be filterable
+        let [width, height, depth] = colliders.sdf_atlas.size;
+        let sdf_atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cloth SDF Atlas Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let sdf_atlas_view = sdf_atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let springs_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Springs Buffer"),
+            contents: bytemuck::cast_slice(&springs.springs),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let spring_ranges_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Spring Ranges Buffer"),
+            contents: bytemuck::cast_slice(&springs.ranges),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let triangles_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Triangles Buffer"),
+            contents: bytemuck::cast_slice(&adjacency.triangles),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let triangle_ranges_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Triangle Ranges Buffer"),
+            contents: bytemuck::cast_slice(&adjacency.ranges),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let vertex_triangles_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Vertex Triangles Buffer"),
+            contents: bytemuck::cast_slice(&adjacency.vertex_triangles),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // The triangles of the mesh colliders and their grid. Only the size of the cells is
+        // taken from the CPU, the cells are filled by mesh_grid.wgsl.
+        let nb_cells = mesh_colliders.grid.nb_cells as u64;
+        let nb_cell_triangles = mesh_colliders.cell_triangles.len().max(1) as u64;
+        let grid_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Mesh Grid Buffer"),
+            contents: bytemuck::cast_slice(&[mesh_colliders.grid]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let mesh_triangles_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Mesh Triangles Buffer"),
+            contents: bytemuck::cast_slice(&mesh_colliders.buffer_contents()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let cell_counts_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cloth Mesh Cell Counts Buffer"),
+            size: nb_cells * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let cell_offsets_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cloth Mesh Cell Offsets Buffer"),
+            size: (nb_cells + 1) * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let cell_triangles_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cloth Mesh Cell Triangles Buffer"),
+            size: nb_cell_triangles * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // =====================================================================
+        //                              Pipelines
+        // =====================================================================
+        // Both shaders declare the same first 3 bind groups, an explicit layout lets them share
+        // the bind groups: 0 vertices, 1 velocities, forces and inverse masses, 2 compute data
+        // and colliders. Group 3 holds the springs and spring ranges for forces.wgsl, and the
+        // mesh colliders for compute.wgsl, so each stays within 8 storage buffers.
+        let bind_group_layouts = [
+            storage_layout(device, "Cloth Vertices Layout", &[false]),
+            storage_layout(device, "Cloth Velocities Layout", &[false, false, true]),
+            parameters_layout(device, "Cloth Compute Data Layout"),
+            storage_layout(device, "Cloth Springs Layout", &[true, true]),
+        ];
+        let mesh_colliders_layout = mesh_layout(device, "Cloth Mesh Colliders Layout", &[true, true, true]);
+        let forces_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Forces Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts[0],
+                &bind_group_layouts[1],
+                &bind_group_layouts[2],
+                &bind_group_layouts[3],
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts[0],
+                &bind_group_layouts[1],
+                &bind_group_layouts[2],
+                &mesh_colliders_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // mesh_grid.wgsl has its own layout: the grid, the triangles, then the cell counts,
+        // offsets and triangles it writes
+        let mesh_grid_layout = mesh_layout(device, "Cloth Mesh Grid Layout", &[true, false, false, false]);
+        let mesh_grid_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Mesh Grid Pipeline Layout"),
+            bind_group_layouts: &[&mesh_grid_layout],
+            push_constant_ranges: &[],
+        });
+        let mesh_grid_pipelines = ["count_main", "scan_main", "fill_main"].map(|entry_point| {
+            compute_pipeline(
+                device,
+                "Cloth Mesh Grid Compute Pipeline",
+                MESH_GRID_SHADER,
+                entry_point,
+                &mesh_grid_pipeline_layout,
+            )
+        });
+
+        // normals.wgsl only needs the vertices and the triangles, it has its own layout
+        // sharing the vertices bind group: 0 vertices, 1 triangles
+        let triangles_layout = storage_layout(device, "Cloth Triangles Layout", &[true, true, true]);
+        let normals_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Normals Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts[0], &triangles_layout],
+            push_constant_ranges: &[],
+        });
+        let normals_compute_pipeline = compute_pipeline(
+            device,
+            "Cloth Normals Compute Pipeline",
+            NORMALS_SHADER,
+            "main",
+            &normals_pipeline_layout,
+        );
+        let forces_compute_pipeline = compute_pipeline(
+            device,
+            "Cloth Forces Compute Pipeline",
+            FORCES_SHADER,
+            "main",
+            &forces_pipeline_layout,
+        );
+        let integration_pipeline = compute_pipeline(
+            device,
+            "Cloth Compute Pipeline",
+            COMPUTE_SHADER,
+            "main",
+            &pipeline_layout,
+        );
+
+        let triangles_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cloth Triangles Bind Group"),
+            layout: &triangles_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: triangles_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: triangle_ranges_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: vertex_triangles_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        // interpolate.wgsl: the vertices before and after the last step blended into the
+        // vertices that are drawn, both copies start as the initial cloth
+        let interpolation = {
+            let previous_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cloth Previous Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            });
+            let render_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cloth Render Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            });
+            let data_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cloth Interpolation Data Buffer"),
+                contents: bytemuck::cast_slice(&[InterpolationData { alpha: 1.0, nb_vertices }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let layout = mesh_layout(device, "Cloth Interpolation Layout", &[true, true, false]);
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Cloth Interpolation Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+            Interpolation {
+                pipeline: compute_pipeline(device, "Cloth Interpolation Compute Pipeline", INTERPOLATE_SHADER, "main", &pipeline_layout),
+                bind_group: buffers_bind_group(
+                    device,
+                    "Cloth Interpolation Bind Group",
+                    &layout,
+                    &[&data_buffer, &cloth_vertex_buffer, &previous_vertex_buffer, &render_vertex_buffer],
+                ),
+                data_buffer,
+                previous_vertex_buffer,
+                render_vertex_buffer,
+            }
+        };
+
+        // stats.wgsl: the largest speed and strain, read back to choose the sub-steps
+        let adaptive = scene.physics.adaptive_iterations.then(|| {
+            let stats_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Cloth Motion Stats Buffer"),
+                size: 2 * std::mem::size_of::<u32>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Cloth Motion Stats Staging Buffer"),
+                size: stats_buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let layout = storage_layout(device, "Cloth Motion Stats Layout", &[true, true, true, true, true, false]);
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Cloth Motion Stats Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+            AdaptiveSubsteps {
+                controller: SubstepController::new(scene, springs),
+                pipeline: compute_pipeline(device, "Cloth Motion Stats Compute Pipeline", STATS_SHADER, "main", &pipeline_layout),
+                bind_group: buffers_bind_group(
+                    device,
+                    "Cloth Motion Stats Bind Group",
+                    &layout,
+                    &[
+                        &cloth_vertex_buffer,
+                        &cloth_velocities_buffer,
+                        &inverse_masses_buffer,
+                        &springs_buffer,
+                        &spring_ranges_buffer,
+                        &stats_buffer,
+                    ],
+                ),
+                stats_buffer,
+                staging_buffer,
+                pending: None,
+                error: None,
+            }
+        });
+        let n_iterations = match &adaptive {
+            Some(adaptive) => adaptive.controller.iterations(),
+            None => scene.physics.iterations,
+        };
+
+        let mesh_bind_group = buffers_bind_group(
+            device,
+            "Cloth Mesh Colliders Bind Group",
+            &mesh_colliders_layout,
+            &[&grid_buffer, &mesh_triangles_buffer, &cell_offsets_buffer, &cell_triangles_buffer],
+        );
+        let mesh_grid_bind_group = buffers_bind_group(
+            device,
+            "Cloth Mesh Grid Bind Group",
+            &mesh_grid_layout,
+            &[&grid_buffer, &mesh_triangles_buffer, &cell_counts_buffer, &cell_offsets_buffer, &cell_triangles_buffer],
+        );
+
+        let buffers: [&[&wgpu::Buffer]; 4] = [
+            &[&cloth_vertex_buffer],
+            &[&cloth_velocities_buffer, &cloth_forces_buffer, &inverse_masses_buffer],
+            &[],
+            &[&springs_buffer, &spring_ranges_buffer],
+        ];
+        let bind_groups = [0, 1, 2, 3].map(|i| match i {
+            // the parameters also hold the texture of the signed distance fields
+            2 => device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Cloth Bind Group"),
+                layout: &bind_group_layouts[i],
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: compute_data_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: colliders_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&sdf_atlas_view),
+                    },
+                ],
+            }),
+            _ => buffers_bind_group(device, "Cloth Bind Group", &bind_group_layouts[i], buffers[i]),
+        });
+
+        // The XPBD solver reuses the first 3 bind groups, the forces buffer holds the positions
+        // at the start of the sub-step. Group 3 is the mesh colliders for the predict and
+        // finalize passes, and the constraints of one color at bindings 4 and 5 for the
+        // projection, after the bindings of the mesh colliders declared by collisions.wgsl.
+        let xpbd = match scene.physics.solver {
+            Solver::MassSpring | Solver::Implicit => None,
+            Solver::Xpbd => {
+                let constraints = XpbdConstraints::new(springs, &indices.to_u32(), &vertices, &scene.physics);
+                let constraints_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Cloth XPBD Constraints Buffer"),
+                    contents: bytemuck::cast_slice(&constraints.constraints),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+                let constraints_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Cloth XPBD Constraints Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 5,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+                let project_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Cloth XPBD Project Pipeline Layout"),
+                    bind_group_layouts: &[
+                        &bind_group_layouts[0],
+                        &bind_group_layouts[1],
+                        &bind_group_layouts[2],
+                        &constraints_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+                let color_bind_groups = constraints
+                    .colors
+                    .iter()
+                    .map(|color| {
+                        let color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Cloth XPBD Color Buffer"),
+                            contents: bytemuck::cast_slice(&[*color]),
+                            usage: wgpu::BufferUsages::UNIFORM,
+                        });
+                        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("Cloth XPBD Color Bind Group"),
+                            layout: &constraints_layout,
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 4,
+                                    resource: color_buffer.as_entire_binding(),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 5,
+                                    resource: constraints_buffer.as_entire_binding(),
+                                },
+                            ],
+                        });
+                        (bind_group, color.count)
+                    })
+                    .collect();
+                Some(XpbdPipelines {
+                    predict_pipeline: compute_pipeline(device, "Cloth XPBD Predict Pipeline", XPBD_SHADER, "predict_main", &pipeline_layout),
+                    project_pipeline: compute_pipeline(device, "Cloth XPBD Project Pipeline", XPBD_SHADER, "project_main", &project_pipeline_layout),
+                    finalize_pipeline: compute_pipeline(device, "Cloth XPBD Finalize Pipeline", XPBD_SHADER, "finalize_main", &pipeline_layout),
+                    color_bind_groups,
+                })
+            }
+        };
+
+        // implicit.wgsl and integrators.wgsl do not handle the collisions, their group 2 is the
+        // compute data alone so that they have room for their own buffers in group 3
+        let data_layout = mesh_layout(device, "Cloth Data Layout", &[]);
+        let data_bind_group = buffers_bind_group(device, "Cloth Data Bind Group", &data_layout, &[&compute_data_buffer]);
+
+        // The implicit solver takes the forces of forces.wgsl and replaces them before compute.wgsl.
+        // Its group 3 is the springs followed by the 6 vectors of the conjugate gradient and its
+        // scalars, so it stays within 8 storage buffers.
+        let implicit = match scene.physics.solver {
+            Solver::MassSpring | Solver::Xpbd => None,
+            Solver::Implicit => {
+                let vectors_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Cloth Implicit Vectors Buffer"),
+                    size: 6 * 3 * 4 * nb_vertices as u64,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+                // rz, rz_start, alpha and beta, then one partial sum per workgroup
+                let state_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Cloth Implicit State Buffer"),
+                    size: (4 + nb_vertices.div_ceil(128) as u64) * 4,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+                let solver_layout = storage_layout(device, "Cloth Implicit Solver Layout", &[true, true, false, false]);
+                let implicit_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Cloth Implicit Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layouts[0], &bind_group_layouts[1], &data_layout, &solver_layout],
+                    push_constant_ranges: &[],
+                });
+                let pipelines = [
+                    "init_main",
+                    "reduce_start_main",
+                    "product_main",
+                    "reduce_alpha_main",
+                    "update_main",
+                    "reduce_beta_main",
+                    "direction_main",
+                    "apply_main",
+                ]
+                .map(|entry_point| {
+                    compute_pipeline(device, "Cloth Implicit Compute Pipeline", IMPLICIT_SHADER, entry_point, &implicit_pipeline_layout)
+                });
+                let bind_group = buffers_bind_group(
+                    device,
+                    "Cloth Implicit Solver Bind Group",
+                    &solver_layout,
+                    &[&springs_buffer, &spring_ranges_buffer, &vectors_buffer, &state_buffer],
+                );
+                Some(ImplicitPipelines {
+                    pipelines,
+                    bind_group,
+                    cg_iterations: scene.physics.cg_iterations,
+                })
+            }
+        };
+
+        // Verlet and RK4 keep their state in one buffer of 4 vectors: the last displacement and
+        // the predicted positions of Verlet, which start at the initial positions, or the positions
+        // and velocities at the start of the RK4 step and the weighted sums of its stage derivatives
+        let integrator = match scene.physics.integrator {
+            Integrator::SymplecticEuler => None,
+            Integrator::Verlet | Integrator::Rk4 => {
+                let mut state = vec![0.0f32; 4 * 3 * nb_vertices as usize];
+                let predicted = 3 * nb_vertices as usize;
+                for (i, vertex) in vertices.iter().enumerate() {
+                    state[predicted + 3 * i..predicted + 3 * i + 3].copy_from_slice(&vertex.position);
+                }
+                let state_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Cloth Integrator State Buffer"),
+                    contents: bytemuck::cast_slice(&state),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+                let state_layout = storage_layout(device, "Cloth Integrator State Layout", &[false]);
+                let integrator_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Cloth Integrator Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layouts[0], &bind_group_layouts[1], &data_layout, &state_layout],
+                    push_constant_ranges: &[],
+                });
+                let entry_points: &[&str] = match scene.physics.integrator {
+                    Integrator::Verlet => &["verlet_main"],
+                    _ => &["rk4_begin_main", "rk4_stage1_main", "rk4_stage2_main", "rk4_stage3_main", "rk4_finish_main"],
+                };
+                Some(IntegratorPipelines {
+                    pipelines: entry_points
+                        .iter()
+                        .map(|entry_point| {
+                            compute_pipeline(device, "Cloth Integrator Compute Pipeline", INTEGRATORS_SHADER, entry_point, &integrator_pipeline_layout)
+                        })
+                        .collect(),
+                    collide_pipeline: compute_pipeline(device, "Cloth Collide Compute Pipeline", COMPUTE_SHADER, "collide_main", &pipeline_layout),
+                    state_bind_group: buffers_bind_group(device, "Cloth Integrator State Bind Group", &state_layout, &[&state_buffer]),
+                })
+            }
+        };
+
+        Self {
+            forces_compute_pipeline,
+            compute_pipeline: integration_pipeline,
+            normals_compute_pipeline,
+            interpolation,
+            adaptive,
+            xpbd,
+            implicit,
+            integrator,
+            data_bind_group,
+            bind_groups,
+            triangles_bind_group,
+            mesh_bind_group,
+            mesh_grid_pipelines,
+            mesh_grid_bind_group,
+            cell_counts_buffer,
+            nb_mesh_triangles: mesh_colliders.grid.nb_triangles,
+            sdf_atlas_texture,
+            initialized: false,
+            cloth_vertex_buffer,
+            cloth_index_buffer,
+            cloth_velocities_buffer,
+            inverse_masses_buffer,
+            inverse_masses: inverse_masses.to_vec(),
+            // the vertices pinned from the start have no mass of their own, they get the one of the scene
+            released_inverse_masses: inverse_masses
+                .iter()
+                .map(|&inverse_mass| if inverse_mass == 0.0 { 1.0 / compute_data.vertex_mass } else { inverse_mass })
+                .collect(),
+            compute_data_buffer,
+            compute_data,
+            colliders_buffer,
+            colliders,
+            nb_vertices,
+            nb_indices: indices.len() as u32,
+            index_format: indices.format(),
+            n_iterations,
+        }
+    }
+
+    // Advance the simulation by one time step, delta_time is split in `n_iterations` iterations:
+    // physics.iterations, or the count chosen by the adaptive sub-steps from the stats of the
+    // last step. The sphere moves once per step, its velocity is used by the collisions during
+    // the step. The cloth before the step is kept for `interpolate`.
+    pub fn step(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, delta_time: f32) {
+        // The sub-steps of this step, from the stats measured at the start of the last one.
+        // Only that small submission is waited for, the last step itself may still be running.
+        // If they cannot be read back the count of the last step is kept, see `last_stats_error`.
+        if let Some(adaptive) = &mut self.adaptive {
+            if let Some((index, receiver)) = adaptive.pending.take() {
+                device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+                let mapped = receiver.try_recv().or_else(|_| {
+                    device.poll(wgpu::Maintain::Wait);
+                    receiver.recv()
+                });
+                match mapped {
+                    Ok(Ok(())) => {
+                        let bits: Vec<u32> = bytemuck::cast_slice(&adaptive.staging_buffer.slice(..).get_mapped_range()).to_vec();
+                        adaptive.staging_buffer.unmap();
+                        let stats = MotionStats {
+                            max_speed: f32::from_bits(bits[0]),
+                            max_strain: f32::from_bits(bits[1]),
+                        };
+                        self.n_iterations = adaptive.controller.choose(delta_time, stats);
+                        adaptive.error = None;
+                    }
+                    Ok(Err(err)) => adaptive.error = Some(err),
+                    // the callback was dropped without being called
+                    Err(_) => adaptive.error = Some(wgpu::BufferAsyncError),
+                }
+            }
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Cloth Previous Vertices Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(
+            &self.cloth_vertex_buffer,
+            0,
+            &self.interpolation.previous_vertex_buffer,
+            0,
+            self.cloth_vertex_buffer.size(),
+        );
+        // The stats of the cloth at the start of this step, for the next one
+        if let Some(adaptive) = &self.adaptive {
+            encoder.clear_buffer(&adaptive.stats_buffer, 0, None);
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Cloth Motion Stats Pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_bind_group(0, &adaptive.bind_group, &[]);
+                compute_pass.set_pipeline(&adaptive.pipeline);
+                compute_pass.dispatch_workgroups((self.nb_vertices as f32 / 128.0).ceil() as u32, 1, 1);
+            }
+            encoder.copy_buffer_to_buffer(&adaptive.stats_buffer, 0, &adaptive.staging_buffer, 0, adaptive.staging_buffer.size());
+        }
+        let index = queue.submit(Some(encoder.finish()));
+        if let Some(adaptive) = &mut self.adaptive {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            adaptive.staging_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            adaptive.pending = Some((index, receiver));
+        }
+
+        self.colliders.advance(delta_time);
+        if !self.colliders.is_empty() {
+            queue.write_buffer(&self.colliders_buffer, 0, bytemuck::cast_slice(&self.colliders.colliders));
+        }
+        self.iterate(device, queue, self.n_iterations, delta_time / self.n_iterations as f32);
+    }
+
+    // Run `iterations` force/integration iterations (or XPBD sub-steps) of `iteration_delta_time` seconds each
+    pub fn iterate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, iterations: u32, iteration_delta_time: f32) {
+        self.compute_data.delta_time = iteration_delta_time;
+        queue.write_buffer(&self.compute_data_buffer, 0, bytemuck::cast_slice(&[self.compute_data]));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Cloth Compute Encoder"),
+        });
+        if !self.initialized {
+            self.build_mesh_grid(&mut encoder);
+            self.upload_sdf_atlas(queue);
+            self.initialized = true;
+        }
+        let workgroups = (self.nb_vertices as f32 / 128.0).ceil() as u32;
+        for _ in 0..iterations {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Compute Pass"),
+                timestamp_writes: None,
+            });
+            for (i, bind_group) in self.bind_groups[..3].iter().enumerate() {
+                compute_pass.set_bind_group(i as u32, bind_group, &[]);
+            }
+            if let Some(xpbd) = &self.xpbd {
+                // XPBD: predict, project the constraints one color after the other, then
+                // the velocities and the collisions
+                compute_pass.set_bind_group(3, &self.mesh_bind_group, &[]);
+                compute_pass.set_pipeline(&xpbd.predict_pipeline);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                compute_pass.set_pipeline(&xpbd.project_pipeline);
+                for (bind_group, count) in &xpbd.color_bind_groups {
+                    compute_pass.set_bind_group(3, bind_group, &[]);
+                    compute_pass.dispatch_workgroups((*count as f32 / 64.0).ceil() as u32, 1, 1);
+                }
+                compute_pass.set_bind_group(3, &self.mesh_bind_group, &[]);
+                compute_pass.set_pipeline(&xpbd.finalize_pipeline);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                continue;
+            }
+            if let Some(integrator) = &self.integrator {
+                self.integrate(integrator, &mut compute_pass, workgroups);
+                continue;
+            }
+            // Pass 1: forces
+            compute_pass.set_bind_group(3, &self.bind_groups[3], &[]);
+            compute_pass.set_pipeline(&self.forces_compute_pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            if let Some(implicit) = &self.implicit {
+                // Implicit solver: the forces become the velocity change of backward Euler
+                self.solve_implicit(implicit, &mut compute_pass, workgroups);
+                compute_pass.set_bind_group(2, &self.bind_groups[2], &[]);
+            }
+            // Pass 2: integration and collisions
+            compute_pass.set_bind_group(3, &self.mesh_bind_group, &[]);
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        {
+            // Pass 3: normals and tangents of the final positions, only needed for the rendering
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Normals Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.bind_groups[0], &[]);
+            compute_pass.set_bind_group(1, &self.triangles_bind_group, &[]);
+            compute_pass.set_pipeline(&self.normals_compute_pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    // Fill `render_vertex_buffer` with the cloth `alpha` of the way between its state before
+    // and after the last step, see FixedTimestep::alpha
+    pub fn interpolate(&self, device: &wgpu::Device, queue: &wgpu::Queue, alpha: f32) {
+        let data = InterpolationData { alpha, nb_vertices: self.nb_vertices };
+        queue.write_buffer(&self.interpolation.data_buffer, 0, bytemuck::cast_slice(&[data]));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Cloth Interpolation Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Interpolation Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.interpolation.bind_group, &[]);
+            compute_pass.set_pipeline(&self.interpolation.pipeline);
+            compute_pass.dispatch_workgroups((self.nb_vertices as f32 / 128.0).ceil() as u32, 1, 1);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    // The conjugate gradient of implicit.wgsl, the scalars are reduced by a single workgroup
+    fn solve_implicit<'a>(&'a self, implicit: &'a ImplicitPipelines, compute_pass: &mut wgpu::ComputePass<'a>, workgroups: u32) {
+        let [init, reduce_start, product, reduce_alpha, update, reduce_beta, direction, apply] = &implicit.pipelines;
+        compute_pass.set_bind_group(2, &self.data_bind_group, &[]);
+        compute_pass.set_bind_group(3, &implicit.bind_group, &[]);
+        compute_pass.set_pipeline(init);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        compute_pass.set_pipeline(reduce_start);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        for _ in 0..implicit.cg_iterations {
+            compute_pass.set_pipeline(product);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            compute_pass.set_pipeline(reduce_alpha);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+            compute_pass.set_pipeline(update);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            compute_pass.set_pipeline(reduce_beta);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+            compute_pass.set_pipeline(direction);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        compute_pass.set_pipeline(apply);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    // One Verlet or RK4 step: every pass of integrators.wgsl but the first of RK4 follows a
+    // forces pass, then the collisions
+    fn integrate<'a>(&'a self, integrator: &'a IntegratorPipelines, compute_pass: &mut wgpu::ComputePass<'a>, workgroups: u32) {
+        for (i, pipeline) in integrator.pipelines.iter().enumerate() {
+            let after_forces = integrator.pipelines.len() == 1 || i > 0;
+            if after_forces {
+                compute_pass.set_bind_group(2, &self.bind_groups[2], &[]);
+                compute_pass.set_bind_group(3, &self.bind_groups[3], &[]);
+                compute_pass.set_pipeline(&self.forces_compute_pipeline);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+            compute_pass.set_bind_group(2, &self.data_bind_group, &[]);
+            compute_pass.set_bind_group(3, &integrator.state_bind_group, &[]);
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        compute_pass.set_bind_group(2, &self.bind_groups[2], &[]);
+        compute_pass.set_bind_group(3, &self.mesh_bind_group, &[]);
+        compute_pass.set_pipeline(&integrator.collide_pipeline);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    // Fill the cells of the mesh colliders grid: count the triangles of every cell, turn the
+    // counts into offsets, then clear the counts and use them as cursors to write the triangles
+    fn build_mesh_grid(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.nb_mesh_triangles == 0 {
+            return;
+        }
+        let workgroups = (self.nb_mesh_triangles as f32 / 64.0).ceil() as u32;
+        let [count_pipeline, scan_pipeline, fill_pipeline] = &self.mesh_grid_pipelines;
+
+        encoder.clear_buffer(&self.cell_counts_buffer, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Mesh Grid Count Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.mesh_grid_bind_group, &[]);
+            compute_pass.set_pipeline(count_pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            compute_pass.set_pipeline(scan_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.clear_buffer(&self.cell_counts_buffer, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cloth Mesh Grid Fill Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.mesh_grid_bind_group, &[]);
+            compute_pass.set_pipeline(fill_pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+    }
+
+    // Copy the signed distance fields of the colliders into their 3D texture
+    fn upload_sdf_atlas(&self, queue: &wgpu::Queue) {
+        let atlas = &self.colliders.sdf_atlas;
+        let [width, height, depth] = atlas.size;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.sdf_atlas_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&atlas.values),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+        );
+    }
+
+    // Pin or release vertices while the simulation runs. A pinned vertex stays where it is
+    // when it is pinned, a released one gets back the mass it was created with, or the mass of
+    // the scene when it was pinned from the start.
+    pub fn set_pinned(&mut self, queue: &wgpu::Queue, vertices: &[u32], pinned: bool) {
+        for &vertex in vertices {
+            self.inverse_masses[vertex as usize] = if pinned { 0.0 } else { self.released_inverse_masses[vertex as usize] };
+        }
+        queue.write_buffer(&self.inverse_masses_buffer, 0, bytemuck::cast_slice(&self.inverse_masses));
+    }
+
+    pub fn is_pinned(&self, vertex: u32) -> bool {
+        self.inverse_masses[vertex as usize] == 0.0
+    }
+
+    // Move the sphere by `offset` during the next step, on top of its trajectory
+    pub fn push_sphere(&mut self, offset: [f32; 3]) {
+        self.colliders.push_sphere(offset);
+    }
+
+    // The colliders where they are after the last step, for the rendering.
+    // The sphere of the scene comes first when it is enabled.
+    pub fn colliders(&self) -> &[Collider] {
+        &self.colliders.colliders
+    }
+
+    // The signed distance fields of the colliders, for the rendering of their surface
+    pub fn sdf_atlas(&self) -> &SdfAtlas {
+        &self.colliders.sdf_atlas
+    }
+
+    // Vertices of the cloth (`Vertex` layout), updated in place by the compute shaders
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.cloth_vertex_buffer
+    }
+
+    // Vertices to draw (`Vertex` layout), written by `interpolate`
+    pub fn render_vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.interpolation.render_vertex_buffer
+    }
+
+    // Triangle list indices of the cloth
+    pub fn index_buffer(&self) -> &wgpu::Buffer {
+        &self.cloth_index_buffer
+    }
+
+    // Uint16, or Uint32 when the cloth has more than 65535 vertices
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        self.index_format
+    }
+
+    pub fn nb_indices(&self) -> u32 {
+        self.nb_indices
+    }
+
+    pub fn nb_vertices(&self) -> u32 {
+        self.nb_vertices
+    }
+
+    // Sub-steps of the last step, or of the next one before the first step
+    pub fn iterations(&self) -> u32 {
+        self.n_iterations
+    }
+
+    // The choice of the sub-steps when physics.adaptive_iterations is set
+    pub fn substep_controller(&self) -> Option<&SubstepController> {
+        self.adaptive.as_ref().map(|adaptive| &adaptive.controller)
+    }
+
+    // Why the motion stats of the last step could not be read back, the sub-steps keep their
+    // last count meanwhile. None when they could, or without physics.adaptive_iterations.
+    pub fn last_stats_error(&self) -> Option<&wgpu::BufferAsyncError> {
+        self.adaptive.as_ref().and_then(|adaptive| adaptive.error.as_ref())
+    }
+
+    // Copy the cloth vertices back to the CPU
+    pub fn read_vertices(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<Vertex>, wgpu::BufferAsyncError> {
+        read_buffer(device, queue, &self.cloth_vertex_buffer)
+    }
+
+    // Copy the cloth positions back to the CPU
+    pub fn read_positions(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<[f32; 3]>, wgpu::BufferAsyncError> {
+        Ok(self.read_vertices(device, queue)?.iter().map(|vertex| vertex.position).collect())
+    }
+
+    // Copy the cloth velocities back to the CPU
+    pub fn read_velocities(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<Velocity>, wgpu::BufferAsyncError> {
+        read_buffer(device, queue, &self.cloth_velocities_buffer)
+    }
+}
+
+// Copy a storage buffer into a mappable buffer and wait for the GPU to read it back
+pub fn read_buffer<T: bytemuck::Pod>(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> Result<Vec<T>, wgpu::BufferAsyncError> {
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Read Back Buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Read Back Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    let slice = staging_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("the map callback is always called by poll(Wait)")?;
+
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    staging_buffer.unmap();
+    Ok(data)
+}
+
+// One storage buffer binding per entry of `read_only`, numbered from 0
+fn storage_layout(device: &wgpu::Device, label: &str, read_only: &[bool]) -> wgpu::BindGroupLayout {
+    let entries: Vec<wgpu::BindGroupLayoutEntry> = read_only
+        .iter()
+        .enumerate()
+        .map(|(binding, &read_only)| wgpu::BindGroupLayoutEntry {
+            binding: binding as u32,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        })
+        .collect();
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &entries,
+    })
+}
+
+// The compute data uniform at binding 0, the read-only colliders at binding 1 and their
+// signed distance fields at binding 2
+fn parameters_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
+    let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            entry(0, wgpu::BufferBindingType::Uniform),
+            entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+// A uniform at binding 0 followed by one storage buffer per entry of `read_only`
+fn mesh_layout(device: &wgpu::Device, label: &str, read_only: &[bool]) -> wgpu::BindGroupLayout {
+    let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let mut entries = vec![entry(0, wgpu::BufferBindingType::Uniform)];
+    for (i, &read_only) in read_only.iter().enumerate() {
+        entries.push(entry(i as u32 + 1, wgpu::BufferBindingType::Storage { read_only }));
+    }
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &entries,
+    })
+}
+
+// One buffer per binding, numbered from 0
+fn buffers_bind_group(device: &wgpu::Device, label: &str, layout: &wgpu::BindGroupLayout, buffers: &[&wgpu::Buffer]) -> wgpu::BindGroup {
+    let entries: Vec<wgpu::BindGroupEntry> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn compute_pipeline(device: &wgpu::Device, label: &str, source: &str, entry_point: &str, layout: &wgpu::PipelineLayout) -> wgpu::ComputePipeline {
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        module: &module,
+        entry_point,
+    })
+}
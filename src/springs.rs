@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::scene::{PhysicsConfig, Scene};
+
+// =========================================================================================
+//                                   SPRING TOPOLOGY
+// =========================================================================================
+// The springs are stored in a CSR layout: the springs of a vertex are contiguous in the
+// springs buffer, and `ranges[vertex]` gives their offset and count. Every spring is stored
+// twice, once in the list of each of its two vertices, so that every vertex only writes its
+// own force. Any connectivity can be described with `from_edges`, without padding springs.
+// =========================================================================================
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpringType {
+    Structural = 0,
+    Shear = 1,
+    Bend = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Spring {
+    pub index1: u32, // the vertex owning this spring
+    pub index2: u32, // the other end of the spring
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+    pub spring_type: u32, // a `SpringType`
+}
+
+// The springs of one vertex: springs[offset..offset + count]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpringRange {
+    pub offset: u32,
+    pub count: u32,
+}
+
+pub struct SpringTopology {
+    pub springs: Vec<Spring>,
+    pub ranges: Vec<SpringRange>,
+}
+
+// A spring between two vertices, before it is split into the lists of its two vertices
+#[derive(Copy, Clone, Debug)]
+pub struct SpringEdge {
+    pub vertex1: u32,
+    pub vertex2: u32,
+    pub rest_length: f32,
+    pub spring_type: SpringType,
+}
+
+// A spring with an end past the last vertex of the mesh
+#[derive(Debug)]
+pub struct SpringError {
+    pub edge: usize, // index of the spring in the list given to `from_edges`
+    pub vertex: u32,
+    pub nb_vertices: u32,
+}
+
+impl fmt::Display for SpringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spring {} uses vertex {}, out of range for a mesh of {} vertices", self.edge, self.vertex, self.nb_vertices)
+    }
+}
+
+impl std::error::Error for SpringError {}
+
+impl SpringTopology {
+    // Build the CSR layout from a list of springs, the stiffness and the damping of every
+    // spring come from the physics parameters of its type. Both ends of every spring are
+    // checked against the vertex count first.
+    pub fn from_edges(nb_vertices: u32, edges: &[SpringEdge], physics: &PhysicsConfig) -> Result<Self, SpringError> {
+        for (i, edge) in edges.iter().enumerate() {
+            if let Some(vertex) = [edge.vertex1, edge.vertex2].into_iter().find(|&vertex| vertex >= nb_vertices) {
+                return Err(SpringError { edge: i, vertex, nb_vertices });
+            }
+        }
+
+        let mut per_vertex: Vec<Vec<Spring>> = vec![Vec::new(); nb_vertices as usize];
+        for edge in edges {
+            let (stiffness, damping) = match edge.spring_type {
+                SpringType::Structural => (physics.structural_stiffness, physics.structural_damping),
+                SpringType::Shear => (physics.shear_stiffness, physics.shear_damping),
+                SpringType::Bend => (physics.bend_stiffness, physics.bend_damping),
+            };
+            for (owner, other) in [(edge.vertex1, edge.vertex2), (edge.vertex2, edge.vertex1)] {
+                per_vertex[owner as usize].push(Spring {
+                    index1: owner,
+                    index2: other,
+                    rest_length: edge.rest_length,
+                    stiffness,
+                    damping,
+                    spring_type: edge.spring_type as u32,
+                });
+            }
+        }
+
+        let mut springs = Vec::with_capacity(edges.len() * 2);
+        let mut ranges = Vec::with_capacity(nb_vertices as usize);
+        for vertex_springs in per_vertex {
+            ranges.push(SpringRange {
+                offset: springs.len() as u32,
+                count: vertex_springs.len() as u32,
+            });
+            springs.extend(vertex_springs);
+        }
+
+        // wgpu does not accept empty storage buffers
+        if springs.is_empty() {
+            springs.push(bytemuck::Zeroable::zeroed());
+        }
+
+        Ok(Self { springs, ranges })
+    }
+
+    // Structural, shear and bend springs of the rectangular grid cloth of the scene.
+    // Rows go along x and columns along z, with the vertex indices of `create_cloth_vertices`.
+    pub fn grid(scene: &Scene) -> Self {
+        let [n_rows, n_cols] = scene.cloth.resolution;
+        let [step_x, step_z] = scene.cloth.cell_size();
+        let diagonal = (step_x * step_x + step_z * step_z).sqrt();
+        let index = |row: u32, col: u32| row * n_cols + col;
+        let mut edges = Vec::new();
+
+        for row in 0..n_rows {
+            for col in 0..n_cols {
+                // Structural springs: neighbors along z and along x
+                if col + 1 < n_cols {
+                    edges.push(edge(index(row, col), index(row, col + 1), step_z, SpringType::Structural));
+                }
+                if row + 1 < n_rows {
+                    edges.push(edge(index(row, col), index(row + 1, col), step_x, SpringType::Structural));
+                }
+                // Shear springs: both diagonals of the cell
+                if row + 1 < n_rows && col + 1 < n_cols {
+                    edges.push(edge(index(row, col), index(row + 1, col + 1), diagonal, SpringType::Shear));
+                    edges.push(edge(index(row, col + 1), index(row + 1, col), diagonal, SpringType::Shear));
+                }
+                // Bend springs: vertices that are two steps apart
+                if col + 2 < n_cols {
+                    edges.push(edge(index(row, col), index(row, col + 2), step_z * 2.0, SpringType::Bend));
+                }
+                if row + 2 < n_rows {
+                    edges.push(edge(index(row, col), index(row + 2, col), step_x * 2.0, SpringType::Bend));
+                }
+            }
+        }
+
+        Self::from_edges(scene.nb_cloth_vertices(), &edges, &scene.physics).expect("the grid springs are always in range")
+    }
+}
+
+fn edge(vertex1: u32, vertex2: u32, rest_length: f32, spring_type: SpringType) -> SpringEdge {
+    SpringEdge {
+        vertex1,
+        vertex2,
+        rest_length,
+        spring_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_triangles_are_stored_once_per_vertex() {
+        // triangles (0, 1, 2) and (1, 3, 2), the diagonal 1-2 is shared
+        let physics = PhysicsConfig::default();
+        let edges = [
+            edge(0, 1, 1.0, SpringType::Structural),
+            edge(1, 2, 1.5, SpringType::Shear),
+            edge(2, 0, 1.0, SpringType::Structural),
+            edge(1, 3, 1.0, SpringType::Structural),
+            edge(3, 2, 1.0, SpringType::Structural),
+        ];
+        let topology = SpringTopology::from_edges(4, &edges, &physics).unwrap();
+
+        // every vertex lists its springs contiguously, in the order of the edges
+        let ranges: Vec<(u32, u32)> = topology.ranges.iter().map(|range| (range.offset, range.count)).collect();
+        assert_eq!(ranges, [(0, 2), (2, 3), (5, 3), (8, 2)]);
+        let ends: Vec<(u32, u32)> = topology.springs.iter().map(|spring| (spring.index1, spring.index2)).collect();
+        assert_eq!(ends, [(0, 1), (0, 2), (1, 0), (1, 2), (1, 3), (2, 1), (2, 0), (2, 3), (3, 1), (3, 2)]);
+
+        // both copies of a spring have the parameters of its type
+        for spring in topology.springs.iter().filter(|spring| spring.index1.min(spring.index2) == 1 && spring.index1.max(spring.index2) == 2) {
+            assert_eq!(spring.rest_length, 1.5);
+            assert_eq!(spring.stiffness, physics.shear_stiffness);
+            assert_eq!(spring.damping, physics.shear_damping);
+            assert_eq!(spring.spring_type, SpringType::Shear as u32);
+        }
+    }
+
+    #[test]
+    fn out_of_range_edge_is_rejected() {
+        let edges = [edge(0, 1, 1.0, SpringType::Structural), edge(1, 4, 1.0, SpringType::Bend)];
+        let err = SpringTopology::from_edges(4, &edges, &PhysicsConfig::default()).err().expect("vertex 4 does not exist");
+        assert_eq!((err.edge, err.vertex, err.nb_vertices), (1, 4, 4));
+    }
+}
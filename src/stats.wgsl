@@ -0,0 +1,88 @@
+// Motion statistics compute shader of the adaptive sub-steps (see substeps.rs), run before
+// every time step: the largest speed of the free vertices and the largest strain of their
+// springs, reduced with atomicMax. A non-negative f32 compares like its bits read as an u32,
+// so the maximum of the bits is the bits of the maximum. NaN has larger bits than infinity,
+// a cloth that blew up is reported as such. The buffer is cleared before the pass.
+
+struct Position {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    normal_x: f32,
+    normal_y: f32,
+    normal_z: f32,
+    tangent_x: f32,
+    tangent_y: f32,
+    tangent_z: f32,
+    tex_coords_x: f32,
+    tex_coords_y: f32,
+}
+
+struct Velocity {
+    velocity_x: f32,
+    velocity_y: f32,
+    velocity_z: f32,
+}
+
+struct Spring {
+    vertex_index_1: u32,
+    vertex_index_2: u32,
+    rest_length: f32,
+    stiffness: f32,
+    damping: f32,
+    spring_type: u32,
+}
+
+struct SpringRange {
+    offset: u32,
+    count: u32,
+}
+
+// Bits of two f32, read back as `MotionStats`
+struct Stats {
+    max_speed: atomic<u32>,
+    max_strain: atomic<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> verticiesPositions: array<Position>;
+@group(0) @binding(1) var<storage, read> verticiesVelocities: array<Velocity>;
+@group(0) @binding(2) var<storage, read> inverseMasses: array<f32>; // 0 when pinned
+@group(0) @binding(3) var<storage, read> springsR: array<Spring>;
+@group(0) @binding(4) var<storage, read> springRanges: array<SpringRange>;
+@group(0) @binding(5) var<storage, read_write> stats: Stats;
+
+fn get_position(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn main(@builtin(global_invocation_id) param: vec3<u32>) {
+    // One thread per vertex, there is one spring range per vertex
+    if (param.x >= arrayLength(&springRanges)) {
+        return;
+    }
+
+    if (inverseMasses[param.x] != 0.0) {
+        let velocity = vec3<f32>(verticiesVelocities[param.x].velocity_x, verticiesVelocities[param.x].velocity_y, verticiesVelocities[param.x].velocity_z);
+        atomicMax(&stats.max_speed, bitcast<u32>(length(velocity)));
+    }
+
+    // Every spring is stored for both of its vertices, each one measures its own list
+    var max_strain = 0.0;
+    let range = springRanges[param.x];
+    for (var i = range.offset; i < range.offset + range.count; i++) {
+        let spring = springsR[i];
+        if (spring.rest_length > 0.0) {
+            let distance = length(get_position(spring.vertex_index_1) - get_position(spring.vertex_index_2));
+            let strain = abs(distance - spring.rest_length) / spring.rest_length;
+            // max() may drop a NaN, keep it. The bits are tested: a NaN may be assumed away
+            // and strain != strain folded to false.
+            if ((bitcast<u32>(strain) & 0x7fffffffu) > 0x7f800000u) {
+                max_strain = strain;
+                break;
+            }
+            max_strain = max(max_strain, strain);
+        }
+    }
+    atomicMax(&stats.max_strain, bitcast<u32>(max_strain));
+}
@@ -0,0 +1,143 @@
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    default::Vertex,
+};
+
+use crate::cloth::Velocity;
+use crate::scene::{Scene, Solver};
+use crate::springs::SpringTopology;
+
+// =========================================================================================
+//                                  ADAPTIVE SUB-STEPS
+// =========================================================================================
+// With physics.adaptive_iterations, the number of sub-steps of every time step is chosen from
+// three limits on the sub-step duration, each scaled by physics.courant:
+// - stiffness: the explicit integration of a spring of stiffness k on a mass m is stable
+//   while h < 2 / w (sqrt(1 + z^2) - z), with w = sqrt(k / m) and the damping ratio z. The
+//   stiffest vertex is bounded with the sum of the stiffness and damping of all its springs
+//   (Gershgorin), which never underestimates. The XPBD and implicit solvers are stable at
+//   any time step and skip this limit.
+// - speed: no vertex moves by more than the shortest spring during a sub-step, so the
+//   springs and the collisions see every step of the motion (a CFL condition).
+// - strain: a spring stretched by more than physics.max_strain is beyond what the linear
+//   estimate above covers, the sub-steps are raised in proportion.
+// The speed and the strain are measured at the start of every step (stats.wgsl on the GPU,
+// `motion_stats` on the CPU) and used for the next one, so the GPU reads them back while the
+// step runs instead of waiting for it. The count goes up at once and down by a quarter at
+// most per step, so it does not flicker between two values and the lag of one step is harmless.
+// =========================================================================================
+
+// Largest speed of the free vertices and largest relative stretch of the springs
+#[derive(Copy, Clone, Debug)]
+pub struct MotionStats {
+    pub max_speed: f32,
+    pub max_strain: f32, // |length - rest length| / rest length
+}
+
+pub struct SubstepController {
+    stiffness_time_step: f32, // longest stable sub-step for the springs, infinite when they do not limit it
+    max_displacement: f32,    // distance a vertex may move during a sub-step
+    courant: f32,
+    max_strain: f32,
+    min_iterations: u32,
+    max_iterations: u32,
+    iterations: u32, // the count chosen for the last step
+}
+
+impl SubstepController {
+    pub fn new(scene: &Scene, springs: &SpringTopology) -> Self {
+        let physics = &scene.physics;
+        let stiffness_time_step = match physics.solver {
+            Solver::MassSpring => stiffness_time_step(springs, physics.vertex_mass),
+            Solver::Xpbd | Solver::Implicit => f32::INFINITY,
+        };
+        let shortest_spring = springs
+            .springs
+            .iter()
+            .map(|spring| spring.rest_length)
+            .filter(|&length| length > 0.0)
+            .fold(f32::INFINITY, f32::min);
+        Self {
+            stiffness_time_step,
+            max_displacement: shortest_spring,
+            courant: physics.courant,
+            max_strain: physics.max_strain,
+            min_iterations: physics.min_iterations,
+            max_iterations: physics.max_iterations,
+            iterations: physics.iterations.clamp(physics.min_iterations, physics.max_iterations),
+        }
+    }
+
+    // Number of sub-steps for a step of `time_step` seconds starting in the state of `stats`
+    pub fn choose(&mut self, time_step: f32, stats: MotionStats) -> u32 {
+        if !(stats.max_speed.is_finite() && stats.max_strain.is_finite()) {
+            // the cloth already blew up, nothing can be estimated from it
+            self.iterations = self.max_iterations;
+            return self.iterations;
+        }
+        let mut sub_step = self.stiffness_time_step;
+        if stats.max_speed > 0.0 {
+            sub_step = sub_step.min(self.max_displacement / stats.max_speed);
+        }
+        let mut required = (time_step / (self.courant * sub_step)).ceil();
+        if stats.max_strain > self.max_strain {
+            required *= stats.max_strain / self.max_strain;
+        }
+        let required = (required.ceil() as u32).clamp(self.min_iterations, self.max_iterations);
+
+        self.iterations = if required >= self.iterations {
+            required
+        } else {
+            required.max(self.iterations - self.iterations / 4)
+        };
+        self.iterations
+    }
+
+    // The count chosen for the last step
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    // Longest stable sub-step of the springs alone, infinite for the XPBD and implicit solvers
+    pub fn stiffness_time_step(&self) -> f32 {
+        self.stiffness_time_step
+    }
+}
+
+// Critical time step of the explicit integration, for the vertex with the stiffest springs.
+// The stiffness matrix of a vertex has its diagonal term k and one -k per spring, so its
+// eigenvalues are below 2 sum(k) (Gershgorin), same for the damping.
+fn stiffness_time_step(springs: &SpringTopology, vertex_mass: f32) -> f32 {
+    let mut time_step = f32::INFINITY;
+    for range in &springs.ranges {
+        let vertex_springs = &springs.springs[range.offset as usize..(range.offset + range.count) as usize];
+        let stiffness: f32 = 2.0 * vertex_springs.iter().map(|spring| spring.stiffness).sum::<f32>();
+        let damping: f32 = 2.0 * vertex_springs.iter().map(|spring| spring.damping).sum::<f32>();
+        if stiffness <= 0.0 {
+            continue;
+        }
+        let omega = (stiffness / vertex_mass).sqrt();
+        let ratio = damping / (2.0 * vertex_mass * omega);
+        time_step = time_step.min(2.0 / omega * ((1.0 + ratio * ratio).sqrt() - ratio));
+    }
+    time_step
+}
+
+// Same as stats.wgsl: the largest speed of the free vertices and the largest strain.
+// f32::max ignores NaN, a NaN is counted as infinite so that `choose` sees it.
+pub fn motion_stats(vertices: &[Vertex], velocities: &[Velocity], inverse_masses: &[f32], springs: &SpringTopology) -> MotionStats {
+    let max = |current: f32, value: f32| if value.is_nan() { f32::INFINITY } else { current.max(value) };
+    let mut stats = MotionStats { max_speed: 0.0, max_strain: 0.0 };
+    for (velocity, &inverse_mass) in velocities.iter().zip(inverse_masses) {
+        if inverse_mass != 0.0 {
+            stats.max_speed = max(stats.max_speed, Vector3::from(velocity.velocity).magnitude());
+        }
+    }
+    for spring in &springs.springs {
+        if spring.rest_length > 0.0 {
+            let delta = Vector3::from(vertices[spring.index1 as usize].position) - Vector3::from(vertices[spring.index2 as usize].position);
+            stats.max_strain = max(stats.max_strain, (delta.magnitude() - spring.rest_length).abs() / spring.rest_length);
+        }
+    }
+    stats
+}
@@ -0,0 +1,123 @@
+use crate::scene::Scene;
+
+// =========================================================================================
+//                                    FIXED TIME STEP
+// =========================================================================================
+// The simulation always advances by `physics.time_step` seconds (split in `physics.iterations`
+// sub-steps), whatever the frame rate: the duration of every frame is added to an accumulator
+// and as many whole time steps as it holds are run. The same scene follows the same trajectory
+// at 30, 60 or 144 frames per second, and a frame hitch does not produce one huge step.
+//
+// At most `physics.max_steps_per_frame` steps run in a frame, the time above is dropped: when
+// the GPU cannot keep up, the simulation slows down instead of falling further behind every
+// frame. What is left in the accumulator, less than a step, is the fraction of the next step
+// at which the frame is drawn: the cloth is interpolated between its last two states.
+// =========================================================================================
+
+pub struct FixedTimestep {
+    pub time_step: f32,
+    pub max_steps: u32,     // steps run at most per frame, u32::MAX to never drop any time
+    accumulator: f64,       // seconds not simulated yet, f64 so thousands of frames do not drift
+    dropped_time: f64,      // seconds dropped by the cap since the start
+}
+
+impl FixedTimestep {
+    pub fn new(time_step: f32, max_steps: u32) -> Self {
+        Self {
+            time_step,
+            max_steps,
+            accumulator: 0.0,
+            dropped_time: 0.0,
+        }
+    }
+
+    // The time step and the cap of the scene
+    pub fn from_scene(scene: &Scene) -> Self {
+        Self::new(scene.physics.time_step, scene.physics.max_steps_per_frame)
+    }
+
+    // Add a frame of `frame_time` seconds, returns the number of time steps to run for it
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        let time_step = self.time_step as f64;
+        self.accumulator += frame_time.max(0.0) as f64;
+        let steps = (self.accumulator / time_step).floor();
+        if steps > self.max_steps as f64 {
+            // keep the fraction of a step, so the interpolation does not jump
+            let kept = self.accumulator - steps * time_step;
+            self.dropped_time += self.accumulator - kept - self.max_steps as f64 * time_step;
+            self.accumulator = kept;
+            return self.max_steps;
+        }
+        self.accumulator -= steps * time_step;
+        steps as u32
+    }
+
+    // Fraction of the next step already elapsed, between 0 and 1: the frame is drawn at
+    // `previous state + alpha (current state - previous state)`
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.time_step as f64).clamp(0.0, 1.0) as f32
+    }
+
+    // Seconds of simulation lost because a frame needed more than `max_steps` steps
+    pub fn dropped_time(&self) -> f64 {
+        self.dropped_time
+    }
+}
+
+// Uniform of interpolate.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InterpolationData {
+    pub alpha: f32,
+    pub nb_vertices: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Advance `timestep` by `nb_frames` frames of `frame_time`, returns the total number of steps
+    fn run(timestep: &mut FixedTimestep, frame_time: f32, nb_frames: u32) -> u32 {
+        (0..nb_frames)
+            .map(|_| {
+                let steps = timestep.advance(frame_time);
+                let alpha = timestep.alpha();
+                assert!((0.0..1.0).contains(&alpha), "alpha {} out of [0, 1)", alpha);
+                steps
+            })
+            .sum()
+    }
+
+    #[test]
+    fn same_steps_at_any_frame_rate() {
+        // 10 seconds at 30, 60 and 144 Hz, with the 1/60 s step of the default scene
+        let time_step = 1.0 / 60.0;
+        let counts: Vec<u32> = [30, 60, 144]
+            .iter()
+            .map(|&rate| {
+                let mut timestep = FixedTimestep::new(time_step, u32::MAX);
+                // 10 s land exactly on a step, the rounding of the frame times may leave the last
+                // step in the accumulator: the same last half step frame runs it at every rate
+                run(&mut timestep, 1.0 / rate as f32, 10 * rate) + run(&mut timestep, time_step / 2.0, 1)
+            })
+            .collect();
+        assert_eq!(counts, [600, 600, 600]);
+    }
+
+    #[test]
+    fn hitch_is_capped_and_dropped() {
+        let time_step = 0.01;
+        let mut timestep = FixedTimestep::new(time_step, 5);
+        assert_eq!(run(&mut timestep, 0.01, 10), 10);
+        assert_eq!(timestep.dropped_time(), 0.0);
+
+        // a frame of 0.125 s holds 12 steps and a half: 5 run, 7 are dropped, the half is kept
+        assert_eq!(timestep.advance(0.125), 5);
+        assert!((timestep.dropped_time() - 0.07).abs() < 1e-6, "dropped {}", timestep.dropped_time());
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4, "alpha {}", timestep.alpha());
+
+        // back to normal frames, nothing more is dropped
+        assert_eq!(run(&mut timestep, 0.01, 10), 10);
+        assert!((timestep.dropped_time() - 0.07).abs() < 1e-6);
+    }
+}
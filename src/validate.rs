@@ -0,0 +1,106 @@
+use std::fmt;
+
+use wgpu_bootstrap::cgmath::{InnerSpace, Vector3};
+
+use crate::cpu::CpuSolver;
+use crate::headless::{HeadlessDevice, HeadlessError};
+use crate::scene::Scene;
+use crate::simulation::ClothSimulation;
+
+// =========================================================================================
+//                                   CROSS-VALIDATION
+// =========================================================================================
+// Steps the compute shaders and the CPU solver from the same initial state and measures
+// how far apart they end up. Both implement the same model, so any difference larger
+// than the floating point noise is a bug in one of them.
+// =========================================================================================
+
+// Divergence between the GPU and the CPU after the same number of iterations
+pub struct Report {
+    pub iterations: u32,
+    pub max_position: f32,
+    pub mean_position: f32,
+    pub worst_position_vertex: usize,
+    pub max_velocity: f32,
+    pub mean_velocity: f32,
+    pub worst_velocity_vertex: usize,
+}
+
+impl Report {
+    pub fn passes(&self, tolerance: f32) -> bool {
+        // written so that NaN never passes
+        self.max_position <= tolerance && self.max_velocity <= tolerance
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "after {} iterations:", self.iterations)?;
+        writeln!(
+            f,
+            "  position divergence: max {:e} (vertex {}), mean {:e}",
+            self.max_position, self.worst_position_vertex, self.mean_position,
+        )?;
+        write!(
+            f,
+            "  velocity divergence: max {:e} (vertex {}), mean {:e}",
+            self.max_velocity, self.worst_velocity_vertex, self.mean_velocity,
+        )
+    }
+}
+
+// Run `iterations` iterations of `iteration_delta_time` seconds on both solvers and compare
+// them, the compute shaders run on `headless`
+pub fn cross_validate(headless: &HeadlessDevice, scene: &Scene, iterations: u32, iteration_delta_time: f32) -> Result<Report, HeadlessError> {
+    let mut gpu = ClothSimulation::new(&headless.device, scene);
+    gpu.iterate(&headless.device, &headless.queue, iterations, iteration_delta_time);
+    let gpu_vertices = gpu.read_vertices(&headless.device, &headless.queue).map_err(HeadlessError::ReadBack)?;
+    let gpu_velocities = gpu.read_velocities(&headless.device, &headless.queue).map_err(HeadlessError::ReadBack)?;
+
+    let mut cpu = CpuSolver::new(scene);
+    cpu.compute_data.delta_time = iteration_delta_time;
+    for _ in 0..iterations {
+        cpu.iterate();
+    }
+
+    let (max_position, mean_position, worst_position_vertex) = divergence(
+        gpu_vertices.iter().map(|v| v.position),
+        cpu.vertices.iter().map(|v| v.position),
+    );
+    let (max_velocity, mean_velocity, worst_velocity_vertex) = divergence(
+        gpu_velocities.iter().map(|v| v.velocity),
+        cpu.velocities.iter().map(|v| v.velocity),
+    );
+
+    Ok(Report {
+        iterations,
+        max_position,
+        mean_position,
+        worst_position_vertex,
+        max_velocity,
+        mean_velocity,
+        worst_velocity_vertex,
+    })
+}
+
+// Max and mean distance between two lists of vectors, with the index of the worst one.
+// A NaN on either side counts as an infinite divergence.
+fn divergence(a: impl Iterator<Item = [f32; 3]>, b: impl Iterator<Item = [f32; 3]>) -> (f32, f32, usize) {
+    let mut max = 0.0;
+    let mut sum = 0.0;
+    let mut worst = 0;
+    let mut count = 0;
+    for (i, (a, b)) in a.zip(b).enumerate() {
+        let mut distance = (Vector3::from(a) - Vector3::from(b)).magnitude();
+        if distance.is_nan() {
+            distance = f32::INFINITY;
+        }
+        if distance > max {
+            max = distance;
+            worst = i;
+        }
+        sum += distance;
+        count += 1;
+    }
+    (max, sum / count.max(1) as f32, worst)
+}
@@ -10,12 +10,21 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> matrices: CameraUniform;
 
+// Model matrix placing the mesh
+struct Model {
+    model: mat4x4<f32>,
+}
+
+// Binding the model uniform to group 1, binding 0
+@group(1) @binding(0)
+var<uniform> transform: Model;
+
 // Structure representing the input to the vertex shader
 struct VertexInput {
     @location(0) position: vec3<f32>, // Vertex position in object space
-    @location(1) normal: vec3<f32>,   // Vertex normal for potential lighting calculations
-    @location(2) tangent: vec3<f32>,  // Tangent vector for advanced texturing (not used here)
-    @location(3) tex_coords: vec2<f32>, // Texture coordinates for sampling textures (not used here)
+    @location(1) normal: vec3<f32>,   // Vertex normal, used to lift the lines off the surface
+    @location(2) tangent: vec3<f32>,  // Tangent vector (not used here)
+    @location(3) tex_coords: vec2<f32>, // Texture coordinates (not used here)
 }
 
 // Structure representing the output from the vertex shader
@@ -29,9 +38,9 @@ fn vs_main(
     model: VertexInput, // Input vertex attributes
 ) -> VertexOutput {
     var out: VertexOutput; // Declare the output variable
-    // Transform the vertex position from object space to clip space
-    // The position is scaled by 0.95 to slightly shrink the object
-    out.clip_position = matrices.proj * matrices.view * vec4<f32>(model.position * 0.95, 1.0);
+    // Move the lines slightly outwards so that they are not hidden by the shaded triangles
+    let position = model.position + model.normal * 0.02;
+    out.clip_position = matrices.proj * matrices.view * transform.model * vec4<f32>(position, 1.0);
     return out; // Return the transformed position for the rasterizer
 }
 
@@ -40,6 +49,6 @@ fn vs_main(
 // The fragment shader entry point
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Outputs a solid blue color with full opacity for every pixel
-    return vec4(0.0, 0.0, 1.0, 1.0); // RGBA: Blue color
-}
\ No newline at end of file
+    // Outputs dark lines over the shaded surface
+    return vec4(0.05, 0.05, 0.2, 1.0);
+}
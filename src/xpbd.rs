@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    default::Vertex,
+};
+
+use crate::scene::PhysicsConfig;
+use crate::springs::{SpringTopology, SpringType};
+
+// =========================================================================================
+//                                     XPBD SOLVER
+// =========================================================================================
+// Extended Position-Based Dynamics: instead of springs pushing the vertices through forces,
+// the cloth is held by constraints that move the vertices back towards their rest state.
+// The compliance of a constraint (the inverse of a stiffness) decides how much of the error
+// is corrected in one sub-step, so a stiff cloth stays stable with a few sub-steps instead
+// of the hundreds the explicit springs need. There are two kinds of constraints:
+// - distance: every structural and shear spring keeps its rest length, compliance in m/N
+// - bending: every pair of triangles sharing an edge keeps its rest dihedral angle, the
+//   angle between their normals around the edge, compliance in rad/(N m). The gradients are
+//   the ones of Bridson et al. 2003 used by the dihedral constraint of Müller et al. 2007.
+//   The bend springs of the grid are not used, they only approximated this.
+//
+// Every sub-step (xpbd.wgsl):
+// 1. predict:  v = v + g * dt, x_prev = x, x = x + v * dt
+// 2. project:  one pass per color, every constraint of the color is solved in parallel
+// 3. finalize: v = (x - x_prev) / dt, damping, then the collisions of collisions.wgsl
+// With a single projection per sub-step the Lagrange multipliers of XPBD always start at
+// zero, so they do not need to be stored ("Small Steps in Physics Simulation", Macklin 2019).
+//
+// Two constraints sharing a vertex cannot be solved at the same time. The constraints of
+// both kinds are colored greedily so that the constraints of a color share no vertex, and
+// sorted by color.
+// =========================================================================================
+
+// Kinds of constraints, same values as in xpbd.wgsl
+pub const CONSTRAINT_DISTANCE: u32 = 0;
+pub const CONSTRAINT_BENDING: u32 = 1;
+
+// Same layout as `XpbdConstraint` in xpbd.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct XpbdConstraint {
+    pub kind: u32,
+    // distance: the two ends, then unused
+    // bending: the vertices opposite to the shared edge in each triangle, then the edge
+    pub vertices: [u32; 4],
+    pub rest: f32,       // distance: rest length, bending: rest dihedral angle in radians
+    pub compliance: f32, // 0 is perfectly rigid
+}
+
+impl XpbdConstraint {
+    // The vertices moved by the constraint
+    pub fn used_vertices(&self) -> &[u32] {
+        match self.kind {
+            CONSTRAINT_BENDING => &self.vertices,
+            _ => &self.vertices[..2],
+        }
+    }
+}
+
+// The constraints of one color: constraints[offset..offset + count].
+// Same layout as `ColorRange` in xpbd.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorRange {
+    pub offset: u32,
+    pub count: u32,
+}
+
+pub struct XpbdConstraints {
+    pub constraints: Vec<XpbdConstraint>, // sorted by color
+    pub colors: Vec<ColorRange>,
+}
+
+impl XpbdConstraints {
+    // One distance constraint per structural and shear spring, with the compliance of its type,
+    // and one bending constraint per edge shared by two triangles of `triangles` (a triangle
+    // list), whose rest angle is the one of `vertices`. The springs are stored once for each
+    // of their vertices, only the copy owned by the smallest index is kept.
+    pub fn new(springs: &SpringTopology, triangles: &[u32], vertices: &[Vertex], physics: &PhysicsConfig) -> Self {
+        let mut constraints = Vec::new();
+        for spring in springs.springs.iter().filter(|spring| spring.index1 < spring.index2) {
+            let compliance = match spring.spring_type {
+                t if t == SpringType::Structural as u32 => physics.structural_compliance,
+                t if t == SpringType::Shear as u32 => physics.shear_compliance,
+                // replaced by the bending constraints
+                _ => continue,
+            };
+            constraints.push(XpbdConstraint {
+                kind: CONSTRAINT_DISTANCE,
+                vertices: [spring.index1, spring.index2, 0, 0],
+                rest: spring.rest_length,
+                compliance,
+            });
+        }
+
+        let position = |vertex: u32| Vector3::from(vertices[vertex as usize].position);
+        for [opposite1, opposite2, edge1, edge2] in adjacent_triangles(triangles) {
+            let positions = [position(opposite1), position(opposite2), position(edge1), position(edge2)];
+            // a degenerate triangle has no normal, its bending is left to the distance constraints
+            if let Some((rest_angle, _)) = dihedral_angle(positions) {
+                constraints.push(XpbdConstraint {
+                    kind: CONSTRAINT_BENDING,
+                    vertices: [opposite1, opposite2, edge1, edge2],
+                    rest: rest_angle,
+                    compliance: physics.bend_compliance,
+                });
+            }
+        }
+
+        // greedy coloring: the first color not used yet by any of the vertices
+        let nb_vertices = springs.ranges.len();
+        let mut vertex_colors: Vec<Vec<u32>> = vec![Vec::new(); nb_vertices];
+        let mut constraint_colors = Vec::with_capacity(constraints.len());
+        for constraint in &constraints {
+            let used = constraint.used_vertices();
+            let color = (0..)
+                .find(|color| used.iter().all(|&vertex| !vertex_colors[vertex as usize].contains(color)))
+                .expect("there is always a free color");
+            for &vertex in used {
+                vertex_colors[vertex as usize].push(color);
+            }
+            constraint_colors.push(color);
+        }
+
+        // sort by color, keeping the order of the constraints inside a color
+        let nb_colors = constraint_colors.iter().map(|&color| color + 1).max().unwrap_or(0);
+        let mut sorted = Vec::with_capacity(constraints.len());
+        let mut colors = Vec::with_capacity(nb_colors as usize);
+        for color in 0..nb_colors {
+            let offset = sorted.len() as u32;
+            sorted.extend(constraints.iter().zip(&constraint_colors).filter(|(_, &c)| c == color).map(|(constraint, _)| *constraint));
+            colors.push(ColorRange {
+                offset,
+                count: sorted.len() as u32 - offset,
+            });
+        }
+
+        // wgpu does not accept empty storage buffers
+        if sorted.is_empty() {
+            sorted.push(bytemuck::Zeroable::zeroed());
+        }
+
+        Self {
+            constraints: sorted,
+            colors,
+        }
+    }
+}
+
+// Every pair of triangles sharing an edge, as [opposite vertex of the first triangle, opposite
+// vertex of the second one, first end of the edge, second end]. The edge goes from its first
+// to its second end in the first triangle, and the other way in the second one: the triangles
+// must be oriented consistently. The pairs are sorted, so the constraints do not depend on the
+// order of the hash map.
+fn adjacent_triangles(triangles: &[u32]) -> Vec<[u32; 4]> {
+    // the vertex opposite to every directed edge of the triangles
+    let mut opposite = HashMap::new();
+    for triangle in triangles.chunks_exact(3) {
+        for k in 0..3 {
+            opposite.insert((triangle[k], triangle[(k + 1) % 3]), triangle[(k + 2) % 3]);
+        }
+    }
+    let mut pairs: Vec<[u32; 4]> = opposite
+        .iter()
+        .filter(|((a, b), _)| a < b)
+        .filter_map(|(&(a, b), &opposite1)| opposite.get(&(b, a)).map(|&opposite2| [opposite1, opposite2, a, b]))
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+// Signed dihedral angle of the triangles (p0, p2, p3) and (p1, p3, p2) around their shared
+// edge p2 p3, 0 when they are flat, and its gradient with respect to the four positions.
+// Same computation as `project_bending` in xpbd.wgsl. The angle comes from atan2, acos loses
+// all its precision around the flat state. None when the edge or a triangle is degenerate.
+pub fn dihedral_angle(positions: [Vector3<f32>; 4]) -> Option<(f32, [Vector3<f32>; 4])> {
+    let [p0, p1, p2, p3] = positions;
+    let edge = p3 - p2;
+    let length = edge.magnitude();
+    if length < 1e-6 {
+        return None;
+    }
+    let n1 = (p2 - p0).cross(p3 - p0);
+    let n2 = (p3 - p1).cross(p2 - p1);
+    let (area1, area2) = (n1.magnitude2(), n2.magnitude2());
+    if area1 < 1e-12 || area2 < 1e-12 {
+        return None;
+    }
+    let (m1, m2) = (n1 / area1, n2 / area2);
+    let gradients = [
+        length * m1,
+        length * m2,
+        (p0 - p3).dot(edge) / length * m1 + (p1 - p3).dot(edge) / length * m2,
+        (p2 - p0).dot(edge) / length * m1 + (p2 - p1).dot(edge) / length * m2,
+    ];
+    let (n1, n2) = (n1.normalize(), n2.normalize());
+    let angle = (-n1.cross(n2).dot(edge / length)).atan2(n1.dot(n2));
+    Some((angle, gradients))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dihedral_gradients_match_finite_differences() {
+        // two triangles folded by about 40 degrees around the z axis
+        let positions = [
+            Vector3::new(-1.0, 0.1, 0.3),
+            Vector3::new(0.8, 0.7, 0.6),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        let (angle, gradients) = dihedral_angle(positions).unwrap();
+        let h = 1e-3;
+        for (vertex, gradient) in gradients.iter().enumerate() {
+            for axis in 0..3 {
+                let mut moved = positions;
+                moved[vertex][axis] += h;
+                let (moved_angle, _) = dihedral_angle(moved).unwrap();
+                let numeric = (moved_angle - angle) / h;
+                assert!((numeric - gradient[axis]).abs() < 1e-2, "vertex {} axis {}: {} != {}", vertex, axis, numeric, gradient[axis]);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_pair_has_no_angle() {
+        let positions = [
+            Vector3::new(-1.0, 0.0, 0.5),
+            Vector3::new(1.0, 0.0, 0.5),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        assert_eq!(dihedral_angle(positions).unwrap().0, 0.0);
+    }
+
+    #[test]
+    fn constraints_of_a_color_share_no_vertex() {
+        // a 4 x 4 grid of two triangles per cell, like the cloth
+        let n = 4;
+        let index = |i: u32, j: u32| i * n + j;
+        let mut triangles = Vec::new();
+        let mut vertices = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                vertices.push(Vertex {
+                    position: [i as f32, 0.0, j as f32],
+                    normal: [0.0, 1.0, 0.0],
+                    tangent: [1.0, 0.0, 0.0],
+                    tex_coords: [0.0, 0.0],
+                });
+                if i + 1 < n && j + 1 < n {
+                    triangles.extend([index(i, j), index(i, j + 1), index(i + 1, j)]);
+                    triangles.extend([index(i, j + 1), index(i + 1, j + 1), index(i + 1, j)]);
+                }
+            }
+        }
+        let springs = SpringTopology::from_edges(n * n, &[], &PhysicsConfig::default()).unwrap();
+        let xpbd = XpbdConstraints::new(&springs, &triangles, &vertices, &PhysicsConfig::default());
+
+        // 3 x 3 cells: 9 diagonals and 12 inner edges of the grid
+        let bending = xpbd.constraints.iter().filter(|constraint| constraint.kind == CONSTRAINT_BENDING);
+        assert_eq!(bending.clone().count(), 21);
+        assert!(bending.clone().all(|constraint| constraint.rest == 0.0));
+        for color in &xpbd.colors {
+            let mut used: Vec<u32> = xpbd.constraints[color.offset as usize..(color.offset + color.count) as usize]
+                .iter()
+                .flat_map(|constraint| constraint.used_vertices().to_vec())
+                .collect();
+            let count = used.len();
+            used.sort();
+            used.dedup();
+            assert_eq!(used.len(), count, "two constraints of a color share a vertex");
+        }
+    }
+}
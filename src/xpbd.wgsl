@@ -0,0 +1,220 @@
+// Compute shader of the XPBD solver (see xpbd.rs), one sub-step is
+// 1. predict_main:  move the vertices with their velocity and the gravity
+// 2. project_main:  once per color, move the vertices of every constraint back to its rest length or angle
+// 3. finalize_main: velocities from the moved positions, then the collisions of collisions.wgsl
+
+// Structure to store positions and related attributes of each vertex
+struct Position {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    normal_x: f32,
+    normal_y: f32,
+    normal_z: f32,
+    tangent_x: f32,
+    tangent_y: f32,
+    tangent_z: f32,
+    tex_coords_x: f32,
+    tex_coords_y: f32,
+}
+
+struct Velocity {
+    velocity_x: f32,
+    velocity_y: f32,
+    velocity_z: f32,
+}
+
+// The forces buffer of the mass-spring solver, used here for the positions at the start of the sub-step
+struct Force {
+    force_x: f32,
+    force_y: f32,
+    force_z: f32,
+}
+
+struct ComputeData {
+    delta_time: f32,
+    nb_vertices: u32,
+    vertex_mass: f32,
+    nb_colliders: u32,
+    friction: f32,
+    restitution: f32,
+    damping: f32,             // Fraction of the velocity removed per second
+    cg_tolerance: f32,
+}
+
+// Kinds of constraints, same values as in xpbd.rs
+const CONSTRAINT_DISTANCE: u32 = 0u;
+const CONSTRAINT_BENDING: u32 = 1u;
+
+// distance: keeps the distance between vertices[0] and vertices[1] at rest
+// bending: keeps the dihedral angle of the triangles (vertices[0], vertices[2], vertices[3])
+// and (vertices[1], vertices[3], vertices[2]) around their shared edge at rest
+struct XpbdConstraint {
+    kind: u32,
+    vertices: array<u32, 4>,
+    rest: f32,
+    compliance: f32,          // Inverse stiffness, 0 is perfectly rigid
+}
+
+// The constraints of the color solved by this pass
+struct ColorRange {
+    offset: u32,
+    count: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> verticiesPositions: array<Position>;
+@group(1) @binding(0) var<storage, read_write> verticiesVelocities: array<Velocity>;
+@group(1) @binding(1) var<storage, read_write> previousPositions: array<Force>;
+@group(1) @binding(2) var<storage, read> inverseMasses: array<f32>; // 0 when pinned
+@group(2) @binding(0) var<uniform> data: ComputeData;
+// Bindings 0 to 3 of group 3 are the mesh colliders of collisions.wgsl
+@group(3) @binding(4) var<uniform> color: ColorRange;
+@group(3) @binding(5) var<storage, read> constraints: array<XpbdConstraint>;
+
+fn get_position(index: u32) -> vec3<f32> {
+    return vec3<f32>(verticiesPositions[index].position_x, verticiesPositions[index].position_y, verticiesPositions[index].position_z);
+}
+
+fn set_position(index: u32, position: vec3<f32>) {
+    verticiesPositions[index].position_x = position.x;
+    verticiesPositions[index].position_y = position.y;
+    verticiesPositions[index].position_z = position.z;
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn predict_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+
+    let position = get_position(param.x);
+    previousPositions[param.x].force_x = position.x;
+    previousPositions[param.x].force_y = position.y;
+    previousPositions[param.x].force_z = position.z;
+
+    // Pinned vertices have an infinite mass: they stay where they are
+    if (inverseMasses[param.x] == 0.0) {
+        verticiesVelocities[param.x].velocity_x = 0.0;
+        verticiesVelocities[param.x].velocity_y = 0.0;
+        verticiesVelocities[param.x].velocity_z = 0.0;
+        return;
+    }
+
+    // v = v + g * dt, the only external force is the gravity
+    verticiesVelocities[param.x].velocity_y += -9.81 * data.delta_time;
+    let velocity = vec3<f32>(verticiesVelocities[param.x].velocity_x, verticiesVelocities[param.x].velocity_y, verticiesVelocities[param.x].velocity_z);
+
+    // x = x + v * dt
+    set_position(param.x, position + velocity * data.delta_time);
+}
+
+// C = |x1 - x2| - rest, dlambda = -C / (w1 + w2 + alpha~)
+fn project_distance(constraint: XpbdConstraint, alpha: f32) {
+    let index1 = constraint.vertices[0];
+    let index2 = constraint.vertices[1];
+    let w1 = inverseMasses[index1];
+    let w2 = inverseMasses[index2];
+    if (w1 + w2 + alpha == 0.0) {
+        return;
+    }
+
+    let x1 = get_position(index1);
+    let x2 = get_position(index2);
+    let delta = x1 - x2;
+    let distance = length(delta);
+    if (distance < 1e-6) {
+        return;
+    }
+    let n = delta / distance;
+
+    let c = distance - constraint.rest;
+    let dlambda = -c / (w1 + w2 + alpha);
+    set_position(index1, x1 + w1 * dlambda * n);
+    set_position(index2, x2 - w2 * dlambda * n);
+}
+
+// C = angle - rest, dlambda = -C / (sum of wi |grad_i C|^2 + alpha~), the same computation as
+// dihedral_angle in xpbd.rs for the angle and its gradients
+fn project_bending(constraint: XpbdConstraint, alpha: f32) {
+    let i0 = constraint.vertices[0];
+    let i1 = constraint.vertices[1];
+    let i2 = constraint.vertices[2];
+    let i3 = constraint.vertices[3];
+    let w0 = inverseMasses[i0];
+    let w1 = inverseMasses[i1];
+    let w2 = inverseMasses[i2];
+    let w3 = inverseMasses[i3];
+    let p0 = get_position(i0);
+    let p1 = get_position(i1);
+    let p2 = get_position(i2);
+    let p3 = get_position(i3);
+
+    let edge = p3 - p2;
+    let edge_length = length(edge);
+    if (edge_length < 1e-6) {
+        return;
+    }
+    let n1 = cross(p2 - p0, p3 - p0);
+    let n2 = cross(p3 - p1, p2 - p1);
+    let area1 = dot(n1, n1);
+    let area2 = dot(n2, n2);
+    if (area1 < 1e-12 || area2 < 1e-12) {
+        return;
+    }
+    let m1 = n1 / area1;
+    let m2 = n2 / area2;
+    let d0 = edge_length * m1;
+    let d1 = edge_length * m2;
+    let d2 = dot(p0 - p3, edge) / edge_length * m1 + dot(p1 - p3, edge) / edge_length * m2;
+    let d3 = dot(p2 - p0, edge) / edge_length * m1 + dot(p2 - p1, edge) / edge_length * m2;
+    let u1 = normalize(n1);
+    let u2 = normalize(n2);
+    // atan2 keeps its precision around the flat state, acos does not
+    let angle = atan2(-dot(cross(u1, u2), edge / edge_length), dot(u1, u2));
+
+    let denominator = w0 * dot(d0, d0) + w1 * dot(d1, d1) + w2 * dot(d2, d2) + w3 * dot(d3, d3) + alpha;
+    if (denominator == 0.0) {
+        return;
+    }
+    let dlambda = -(angle - constraint.rest) / denominator;
+    set_position(i0, p0 + w0 * dlambda * d0);
+    set_position(i1, p1 + w1 * dlambda * d1);
+    set_position(i2, p2 + w2 * dlambda * d2);
+    set_position(i3, p3 + w3 * dlambda * d3);
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn project_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= color.count) {
+        return;
+    }
+    // No other constraint of this color touches these vertices
+    let constraint = constraints[color.offset + param.x];
+    // Compliance scaled by the time step: alpha~ = alpha / dt^2
+    let alpha = constraint.compliance / (data.delta_time * data.delta_time);
+    if (constraint.kind == CONSTRAINT_BENDING) {
+        project_bending(constraint, alpha);
+    } else {
+        project_distance(constraint, alpha);
+    }
+}
+
+@compute @workgroup_size(128, 1, 1)
+fn finalize_main(@builtin(global_invocation_id) param: vec3<u32>) {
+    if (param.x >= data.nb_vertices) {
+        return;
+    }
+    if (inverseMasses[param.x] == 0.0) {
+        return;
+    }
+
+    // v = (x - x_prev) / dt, then the damping
+    let previous = vec3<f32>(previousPositions[param.x].force_x, previousPositions[param.x].force_y, previousPositions[param.x].force_z);
+    let velocity = (get_position(param.x) - previous) / data.delta_time * max(1.0 - data.damping * data.delta_time, 0.0);
+    verticiesVelocities[param.x].velocity_x = velocity.x;
+    verticiesVelocities[param.x].velocity_y = velocity.y;
+    verticiesVelocities[param.x].velocity_z = velocity.z;
+
+    // Handle collision detection and response with every collider, see collisions.wgsl
+    handle_collisions(param.x);
+}
@@ -0,0 +1,26 @@
+// The compute shaders against the CPU solver on the default scene, the same comparison as
+// `--validate`. Skipped when the machine has no wgpu adapter at all.
+
+use cloth_simulation::headless::{HeadlessDevice, HeadlessError};
+use cloth_simulation::validate::cross_validate;
+use cloth_simulation::Scene;
+
+const ITERATIONS: u32 = 300;
+const TOLERANCE: f32 = 1e-3;
+
+#[test]
+fn gpu_matches_cpu_on_the_default_scene() {
+    let headless = match HeadlessDevice::new() {
+        Ok(headless) => headless,
+        Err(HeadlessError::NoAdapter) => {
+            eprintln!("no wgpu adapter available, skipping the cross-validation");
+            return;
+        }
+        Err(err) => panic!("{}", err),
+    };
+
+    let scene = Scene::default();
+    let iteration_delta_time = scene.physics.time_step / scene.physics.iterations as f32;
+    let report = cross_validate(&headless, &scene, ITERATIONS, iteration_delta_time).expect("the GPU state can be read back");
+    assert!(report.passes(TOLERANCE), "{}", report);
+}